@@ -1,9 +1,22 @@
-use crate::Message;
-use anyhow::Result;
-use iced::Task;
-use rodio::{source::Source, Decoder, OutputStream, OutputStreamHandle, Sink};
+use crate::dsp;
+use crate::editor;
+use crate::error::{Error, ErrorType};
+use crate::status::StatusBarLevel;
+use crate::AudioCloud;
+use anyhow::{anyhow, Result};
+use rodio::buffer::SamplesBuffer;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{cpal, source::Source, Decoder, OutputStream, OutputStreamHandle, Sink};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
+pub const DEFAULT_OUTPUT: &str = "System default";
+pub const NO_OUTPUT: &str = "No audio output device";
+const CHUNK_FRAMES: usize = 512;
+
 pub async fn play_audio() -> Result<(), anyhow::Error> {
     Ok(())
 }
@@ -12,26 +25,370 @@ pub struct Handlers {
     pub stream: OutputStream,
     pub stream_handle: OutputStreamHandle,
     pub sink: Sink,
+    pub device: String,
 }
-pub async fn init_audio_unwrap() -> Handlers {
-    init_audio().expect("Couldnt init audio devices")
-}
-pub fn init_audio() -> Result<Handlers, anyhow::Error> {
-    let (stream, stream_handle) = OutputStream::try_default().unwrap();
-    let sink = Sink::try_new(&stream_handle).unwrap();
+// Opens the named output device, falling back to the system default if it isn't connected
+pub fn init_audio(device_name: Option<&str>) -> Result<Handlers, anyhow::Error> {
+    let host = cpal::default_host();
+    let device = device_name
+        .and_then(|name| {
+            host.output_devices()
+                .ok()?
+                .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        })
+        .or_else(|| host.default_output_device())
+        .ok_or(anyhow!("No output device found"))?;
+    let (stream, stream_handle) = OutputStream::try_from_device(&device)?;
+    let sink = Sink::try_new(&stream_handle)?;
     Ok(Handlers {
         stream,
         stream_handle,
         sink,
+        device: device.name()?,
     })
 }
 
-pub fn wait_playback_end(duration: Option<Duration>, now: Instant) -> Task<Message> {
-    Task::perform(wait_for_playback(duration, now), Message::SamplePlayDone)
+fn output_device_names() -> Vec<String> {
+    match cpal::default_host().output_devices() {
+        Err(_) => vec![],
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+    }
+}
+
+// Rebuilds the output on the configured device and picks the loaded sample back up
+pub fn reconnect(app: &mut AudioCloud) {
+    // Some backends refuse to open a device twice, so the old stream goes first
+    let editor_playing = app.editor.is_playing();
+    app.editor.sink = None;
+    app.audio_devices = None;
+    let wanted = app.settings.output_device.clone();
+    let handlers = match init_audio(wanted.as_deref()) {
+        Err(e) => {
+            app.player.is_playing = false;
+            app.status.set(
+                StatusBarLevel::Danger,
+                &format!("Audio output unavailable: {}", e),
+            );
+            return;
+        }
+        Ok(val) => val,
+    };
+    handlers.sink.set_volume(app.player.volume);
+    if let Some(audio) = &app.player.audio {
+        load(
+            &handlers.sink,
+            audio,
+            app.player.looping,
+            app.player.position,
+        );
+        if app.player.is_playing {
+            handlers.sink.play();
+        }
+    }
+    match &wanted {
+        Some(name) if name != &handlers.device => app.status.set(
+            StatusBarLevel::Danger,
+            &format!("{} not found, using {}", name, handlers.device),
+        ),
+        _ => app.status.set(
+            StatusBarLevel::Succes,
+            &format!("Audio output: {}", handlers.device),
+        ),
+    }
+    app.player.last_tick = Instant::now();
+    app.audio_devices = Some(handlers);
+    if editor_playing {
+        editor::start_playback(app);
+    }
+}
+
+pub async fn list_output_devices() -> Vec<String> {
+    tokio::task::spawn_blocking(output_device_names)
+        .await
+        .unwrap_or_default()
+}
+
+// Nothing is retried until the list of devices changes
+pub fn check_device(app: &mut AudioCloud, names: Vec<String>) {
+    if names == app.settings_state.output_devices {
+        return;
+    }
+    let lost = match &app.audio_devices {
+        None => true,
+        Some(devs) => {
+            !names.contains(&devs.device)
+                || matches!(&app.settings.output_device,
+                    Some(wanted) if wanted != &devs.device && names.contains(wanted))
+        }
+    };
+    app.settings_state.output_devices = names;
+    if lost {
+        reconnect(app);
+    }
+}
+
+#[derive(Debug)]
+pub struct Decoded {
+    pub samples: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
 }
+impl Decoded {
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() / self.channels.max(1) as usize;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64)
+    }
+}
+
+pub async fn decode_file(path: String) -> Result<Arc<Decoded>, Error> {
+    tokio::task::spawn_blocking(move || {
+        let file = BufReader::new(File::open(&path).ok()?);
+        let decoder = Decoder::new(file).ok()?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        Some(Arc::new(Decoded {
+            samples: decoder.convert_samples().collect(),
+            channels,
+            sample_rate,
+        }))
+    })
+    .await
+    .ok()
+    .flatten()
+    .ok_or(Error::new(ErrorType::FileOpen))
+}
+
+// The sink is left paused
+pub fn load(sink: &Sink, audio: &Decoded, looping: bool, start: Duration) {
+    let source = SamplesBuffer::new(audio.channels, audio.sample_rate, audio.samples.clone());
+    if !sink.empty() {
+        sink.clear();
+    }
+    sink.pause();
+    if looping {
+        sink.append(source.repeat_infinite().skip_duration(start));
+    } else {
+        sink.append(source.skip_duration(start));
+    }
+}
+
+pub fn seek(sink: &Sink, audio: &Decoded, pos: Duration, looping: bool) {
+    if !looping && !sink.empty() && sink.try_seek(pos).is_ok() {
+        return;
+    }
+    let paused = sink.is_paused();
+    load(sink, audio, looping, pos);
+    if !paused {
+        sink.play();
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+// Positions index the interleaved buffer, region and trim go from 0.0 to 1.0
+pub struct Transport {
+    pub position: AtomicUsize,
+    pub looping: AtomicBool,
+    pub region: RwLock<(f32, f32)>,
+    pub trim: RwLock<(f32, f32)>,
+}
+impl Transport {
+    pub fn new() -> Self {
+        Transport {
+            position: AtomicUsize::new(0),
+            looping: AtomicBool::new(false),
+            region: RwLock::new((0.0, 1.0)),
+            trim: RwLock::new((0.0, 1.0)),
+        }
+    }
+    pub fn region_bounds(&self, len: usize, channels: u16) -> (usize, usize) {
+        let region = self.region.read().map(|r| *r).unwrap_or((0.0, 1.0));
+        dsp::sample_bounds(region, len, channels)
+    }
+    pub fn trim_bounds(&self, len: usize, channels: u16) -> (usize, usize) {
+        let trim = self.trim.read().map(|t| *t).unwrap_or((0.0, 1.0));
+        dsp::sample_bounds(trim, len, channels)
+    }
+    pub fn seek(&self, pos: f32, len: usize, channels: u16) {
+        self.position
+            .store(frame_index(pos, len, channels), Ordering::Relaxed);
+    }
+}
+
+pub fn frame_index(pos: f32, len: usize, channels: u16) -> usize {
+    let channels = channels.max(1) as usize;
+    let frames = len / channels;
+    (pos.clamp(0.0, 1.0) * frames as f32) as usize * channels
+}
+
+// Bypass switches to the original without restarting, for A/B at the same spot
+pub struct BufferSource {
+    audio: Arc<RwLock<Vec<f32>>>,
+    original: Arc<RwLock<Vec<f32>>>,
+    bypass: Arc<AtomicBool>,
+    transport: Arc<Transport>,
+    channels: u16,
+    sample_rate: u32,
+    chunk: Vec<f32>,
+    chunk_pos: usize,
+}
+impl BufferSource {
+    pub fn new(
+        audio: Arc<RwLock<Vec<f32>>>,
+        original: Arc<RwLock<Vec<f32>>>,
+        bypass: Arc<AtomicBool>,
+        transport: Arc<Transport>,
+        channels: u16,
+        sample_rate: u32,
+    ) -> Self {
+        BufferSource {
+            audio,
+            original,
+            bypass,
+            transport,
+            channels: channels.max(1),
+            sample_rate,
+            chunk: vec![],
+            chunk_pos: 0,
+        }
+    }
+
+    // Only the trimmed part plays, seeks apply from the next chunk
+    fn fill(&mut self) -> Option<()> {
+        let len = self.audio.read().ok()?.len();
+        let (low, high) = self.transport.trim_bounds(len, self.channels);
+        let mut pos = self.transport.position.load(Ordering::Relaxed).max(low);
+        let mut end = high;
+        if self.transport.looping.load(Ordering::Relaxed) {
+            let (start, stop) = self.transport.region_bounds(len, self.channels);
+            let (start, stop) = (start.max(low), stop.min(high));
+            if start < stop {
+                if pos >= stop {
+                    pos = start;
+                }
+                end = stop;
+            }
+        }
+        if pos >= end {
+            return None;
+        }
+        let end = (pos + CHUNK_FRAMES * self.channels as usize).min(end);
+        let buffer = match self.bypass.load(Ordering::Relaxed) {
+            true => self.original.read().ok()?,
+            false => self.audio.read().ok()?,
+        };
+        self.chunk.clear();
+        self.chunk
+            .extend((pos..end).map(|i| buffer.get(i).copied().unwrap_or(0.0)));
+        self.transport.position.store(end, Ordering::Relaxed);
+        self.chunk_pos = 0;
+        Some(())
+    }
+}
+impl Iterator for BufferSource {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+        if self.chunk_pos >= self.chunk.len() {
+            self.fill()?;
+        }
+        self.chunk_pos += 1;
+        Some(self.chunk[self.chunk_pos - 1])
+    }
+}
+impl Source for BufferSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// 24 bit PCM, an odd data chunk gets a pad byte
+pub fn encode_wav(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<u8> {
+    let block_align = channels as u32 * 3;
+    let data_len = samples.len() as u32 * 3;
+    let pad = data_len % 2;
+    let mut out = Vec::with_capacity((44 + data_len + pad) as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len + pad).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * block_align).to_le_bytes());
+    out.extend_from_slice(&(block_align as u16).to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32;
+        out.extend_from_slice(&value.to_le_bytes()[..3]);
+    }
+    if pad == 1 {
+        out.push(0);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], i: usize) -> u32 {
+        u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap())
+    }
+    fn u16_at(bytes: &[u8], i: usize) -> u16 {
+        u16::from_le_bytes(bytes[i..i + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn wav_header() {
+        let wav = encode_wav(&[0.0; 8], 2, 48000);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&wav, 16), 16);
+        assert_eq!(u16_at(&wav, 20), 1);
+        assert_eq!(u16_at(&wav, 22), 2);
+        assert_eq!(u32_at(&wav, 24), 48000);
+        assert_eq!(u32_at(&wav, 28), 48000 * 6);
+        assert_eq!(u16_at(&wav, 32), 6);
+        assert_eq!(u16_at(&wav, 34), 24);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40), 24);
+        assert_eq!(wav.len(), 44 + 24);
+    }
+
+    #[test]
+    fn odd_data_chunk_is_padded() {
+        let wav = encode_wav(&[0.5; 3], 1, 44100);
+        assert_eq!(u32_at(&wav, 40), 9);
+        assert_eq!(wav.len(), 44 + 9 + 1);
+        assert_eq!(wav.last(), Some(&0));
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+    }
 
-pub async fn wait_for_playback(duration: Option<Duration>, now: Instant) -> Instant {
-    let dur = duration.unwrap();
-    let _ = std::thread::sleep(dur);
-    now
+    #[test]
+    fn samples_are_clamped_24_bit() {
+        let wav = encode_wav(&[1.0, -1.0, 2.0, 0.0], 1, 44100);
+        let sample = |i: usize| {
+            let b = &wav[44 + i * 3..44 + i * 3 + 3];
+            i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8
+        };
+        assert_eq!(sample(0), 8_388_607);
+        assert_eq!(sample(1), -8_388_607);
+        assert_eq!(sample(2), 8_388_607);
+        assert_eq!(sample(3), 0);
+    }
 }
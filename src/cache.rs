@@ -0,0 +1,368 @@
+use serde_derive::*;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use iced::Task;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::helpers;
+use crate::{AudioCloud, Message, SampleType};
+
+pub const CACHE_DIR: &str = "cached/";
+pub const EXPORT_FOLDER: &str = "/edits/";
+const PREVIEW_DIR: &str = "cached/previews/";
+const PREVIEW_LIMIT: u64 = 256 * 1024 * 1024;
+const INDEX_FILE: &str = "cached/index.json";
+const INDEX_BROKEN: &str = "cached/index.json.broken";
+// Parts written to more recently may still belong to a running download
+const STALE_PART: Duration = Duration::from_secs(60 * 60);
+
+// A save that finishes after a newer one is dropped
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static SAVED: tokio::sync::Mutex<u64> = tokio::sync::Mutex::const_new(0);
+
+// Files are named after their content hash, so shared audio is only stored once
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    pub server: String,
+    pub path: String,
+    pub file: String,
+    pub original_name: String,
+    pub size: u64,
+    pub content_hash: String,
+    pub last_access: u64,
+    #[serde(default)]
+    pub sampletype: Option<SampleType>,
+}
+
+impl CacheEntry {
+    // Exports exist nowhere else and are never evicted
+    pub fn is_export(&self) -> bool {
+        self.path.starts_with(EXPORT_FOLDER)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CacheIndex {
+    pub entries: Vec<CacheEntry>,
+    // Saves wait for this, or the index on disk would be overwritten
+    #[serde(skip)]
+    pub loaded: bool,
+}
+
+impl CacheIndex {
+    pub fn get(&self, server_url: &str, path: &str) -> Option<&CacheEntry> {
+        let server = helpers::server_id(server_url);
+        self.entries
+            .iter()
+            .find(|e| e.server == server && e.path == path)
+    }
+    pub fn contains(&self, server_url: &str, path: &str) -> bool {
+        self.get(server_url, path).is_some()
+    }
+    pub fn insert(&mut self, entry: CacheEntry) {
+        let replaced = self
+            .entries
+            .iter()
+            .position(|e| e.server == entry.server && e.path == entry.path)
+            .map(|i| self.entries.remove(i));
+        self.entries.push(entry);
+        if let Some(old) = replaced {
+            self.remove_unreferenced(&old.file);
+        }
+    }
+    pub fn touch(&mut self, server_url: &str, path: &str) {
+        let server = helpers::server_id(server_url);
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.server == server && e.path == path)
+        {
+            entry.last_access = now();
+        }
+    }
+    pub fn file_path(&self, server_url: &str, path: &str) -> Option<PathBuf> {
+        let entry = self.get(server_url, path)?;
+        fs::canonicalize(Path::new(CACHE_DIR).join(&entry.file)).ok()
+    }
+    // Files shared by several entries are only counted once
+    pub fn download_size(&self) -> u64 {
+        let mut seen = HashSet::new();
+        self.entries
+            .iter()
+            .filter(|e| !e.is_export() && seen.insert(e.file.as_str()))
+            .map(|e| e.size)
+            .sum()
+    }
+    // The most recently used download is always kept
+    pub fn evict(&mut self, limit: u64) {
+        let mut size = self.download_size();
+        while size > limit && self.entries.iter().filter(|e| !e.is_export()).count() > 1 {
+            let oldest = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| !e.is_export())
+                .min_by_key(|(_, e)| e.last_access)
+                .map(|(i, _)| i)
+                .unwrap();
+            let entry = self.entries.remove(oldest);
+            if !self
+                .entries
+                .iter()
+                .any(|e| !e.is_export() && e.file == entry.file)
+            {
+                size -= entry.size;
+            }
+            self.remove_unreferenced(&entry.file);
+        }
+    }
+    pub fn usage_per_server(&self) -> Vec<(String, u64)> {
+        let mut out: Vec<(String, u64)> = vec![];
+        for entry in &self.entries {
+            match out.iter_mut().find(|(server, _)| server == &entry.server) {
+                Some((_, size)) => *size += entry.size,
+                None => out.push((entry.server.clone(), entry.size)),
+            }
+        }
+        out
+    }
+    pub fn purge_server(&mut self, server: &str) {
+        let (purged, kept): (Vec<CacheEntry>, Vec<CacheEntry>) = self
+            .entries
+            .drain(..)
+            .partition(|e| e.server == server && !e.is_export());
+        self.entries = kept;
+        for entry in purged {
+            self.remove_unreferenced(&entry.file);
+        }
+        let _ = fs::remove_dir_all(preview_dir(server));
+    }
+    fn remove_unreferenced(&self, file: &str) {
+        if !self.entries.iter().any(|e| e.file == file) {
+            let _ = fs::remove_file(Path::new(CACHE_DIR).join(file));
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn part_path(server_url: &str, path: &str) -> String {
+    CACHE_DIR.to_string() + &helpers::hash_sample(server_url, path) + ".part"
+}
+
+// One folder per server, so purging a server removes its previews too
+fn preview_dir(server_url: &str) -> String {
+    PREVIEW_DIR.to_string() + &xxh3_64(helpers::server_id(server_url).as_bytes()).to_string() + "/"
+}
+
+pub fn preview_path(server_url: &str, path: &str) -> String {
+    let dir = preview_dir(server_url);
+    if !Path::new(&dir).exists() {
+        let _ = fs::create_dir_all(&dir);
+    }
+    dir + &helpers::hash_sample(server_url, path) + ".audio"
+}
+
+pub fn prune_previews() {
+    let mut previews: Vec<(PathBuf, u64, SystemTime)> = fs::read_dir(PREVIEW_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|dir| fs::read_dir(dir.path()).ok())
+        .flatten()
+        .flatten()
+        .filter_map(|file| {
+            let meta = file.metadata().ok()?;
+            Some((file.path(), meta.len(), meta.modified().ok()?))
+        })
+        .collect();
+    previews.sort_by(|a, b| b.2.cmp(&a.2));
+    let mut size = 0;
+    for (path, len, _) in previews {
+        size += len;
+        if size > PREVIEW_LIMIT {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Downloads from before the index are taken over if known_paths has their path
+pub async fn load_index(server_url: String, known_paths: Vec<String>) -> CacheIndex {
+    tokio::task::spawn_blocking(move || read_index(&server_url, &known_paths))
+        .await
+        .unwrap_or_default()
+}
+
+fn read_index(server_url: &str, known_paths: &[String]) -> CacheIndex {
+    if !Path::new(CACHE_DIR).exists() {
+        let _ = fs::create_dir(CACHE_DIR);
+    }
+    let _ = fs::remove_dir_all(PREVIEW_DIR);
+    let mut index = match fs::read_to_string(INDEX_FILE) {
+        Err(_) => CacheIndex::default(),
+        Ok(content) => match serde_json::from_str::<CacheIndex>(&content) {
+            Ok(val) => val,
+            Err(_) => {
+                let _ = fs::rename(INDEX_FILE, INDEX_BROKEN);
+                CacheIndex::default()
+            }
+        },
+    };
+    index.loaded = true;
+    index.entries.retain(|e| {
+        fs::metadata(Path::new(CACHE_DIR).join(&e.file))
+            .map(|meta| meta.is_file() && meta.len() == e.size)
+            .unwrap_or(false)
+    });
+    // Files of a broken index stay until the cache is reset
+    let sweep = !Path::new(INDEX_BROKEN).exists();
+
+    let files = match fs::read_dir(CACHE_DIR) {
+        Err(_) => return index,
+        Ok(val) => val,
+    };
+    for file in files.flatten() {
+        let name = file.file_name().to_string_lossy().to_string();
+        let is_file = file.file_type().map(|t| t.is_file()).unwrap_or(false);
+        if !is_file || index.entries.iter().any(|e| e.file == name) {
+            continue;
+        }
+        if let Some(entry) = legacy_entry(&name, server_url, known_paths) {
+            index.entries.push(entry);
+            continue;
+        }
+        let stale = match name.ends_with(".part") {
+            false => true,
+            true => file
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > STALE_PART),
+        };
+        if sweep && stale && created_by_cache(&name) {
+            let _ = fs::remove_file(file.path());
+        }
+    }
+    index
+}
+
+fn created_by_cache(name: &str) -> bool {
+    match name.split_once('.') {
+        Some((hash, ext)) => {
+            (1..=20).contains(&hash.len())
+                && hash.chars().all(|c| c.is_ascii_hexdigit())
+                && !ext.contains('.')
+        }
+        None => false,
+    }
+}
+
+// Old downloads are "cached/<xxh3 of the path without .wav>.wav"
+fn legacy_entry(name: &str, server_url: &str, known_paths: &[String]) -> Option<CacheEntry> {
+    let hash = name.strip_suffix(".wav")?;
+    let path = known_paths
+        .iter()
+        .find(|p| xxh3_64(p.replace(".wav", "").as_bytes()).to_string() == hash)?;
+    let legacy = Path::new(CACHE_DIR).join(name);
+    let bytes = fs::read(&legacy).ok()?;
+    let content_hash = format!("{:016x}", xxh3_64(&bytes));
+    let file = content_hash.clone() + ".wav";
+    let target = Path::new(CACHE_DIR).join(&file);
+    if target.exists() {
+        let _ = fs::remove_file(&legacy);
+    } else {
+        fs::rename(&legacy, &target).ok()?;
+    }
+    Some(CacheEntry {
+        server: helpers::server_id(server_url),
+        path: path.clone(),
+        file,
+        original_name: path.rsplit('/').next().unwrap_or(path).to_string(),
+        size: bytes.len() as u64,
+        content_hash,
+        last_access: now(),
+        sampletype: None,
+    })
+}
+
+pub async fn save_index(index: CacheIndex, generation: u64) {
+    let mut saved = SAVED.lock().await;
+    if generation < *saved {
+        return;
+    }
+    let content = serde_json::to_string_pretty(&index).unwrap();
+    if helpers::write_atomic(INDEX_FILE, &content) {
+        *saved = generation;
+    }
+}
+
+pub fn save(app: &AudioCloud) -> Task<Message> {
+    if !app.cache.loaded {
+        return Task::none();
+    }
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    Task::perform(save_index(app.cache.clone(), generation), Message::Nothing)
+}
+
+// deletes all files in "cached" and returns an empty index
+pub async fn clear() -> CacheIndex {
+    let _ = fs::remove_dir_all(CACHE_DIR);
+    let _ = fs::create_dir(CACHE_DIR);
+    CacheIndex {
+        entries: vec![],
+        loaded: true,
+    }
+}
+
+pub fn store(app: &mut AudioCloud, entry: CacheEntry) -> Task<Message> {
+    app.cache.insert(entry);
+    app.cache
+        .evict(app.settings.cache_limit_mb as u64 * 1024 * 1024);
+    save(app)
+}
+
+pub fn touch(app: &mut AudioCloud, path: &str) -> Task<Message> {
+    app.cache.touch(&app.settings.server_url, path);
+    save(app)
+}
+
+pub fn load(app: &AudioCloud) -> Task<Message> {
+    if app.cache.loaded {
+        return Task::none();
+    }
+    let known_paths = app
+        .settings
+        .favourite_samples
+        .iter()
+        .map(|s| s.path.clone())
+        .chain(app.settings.recently_played.iter().cloned())
+        .collect();
+    Task::perform(
+        load_index(app.settings.server_url.clone(), known_paths),
+        Message::CacheLoaded,
+    )
+}
+
+// Entries stored before the index was read are merged into it
+pub fn loaded(app: &mut AudioCloud, index: CacheIndex) -> Task<Message> {
+    let pending = std::mem::replace(&mut app.cache, index);
+    if pending.entries.is_empty() {
+        return save(app);
+    }
+    for entry in pending.entries {
+        app.cache.insert(entry);
+    }
+    app.cache
+        .evict(app.settings.cache_limit_mb as u64 * 1024 * 1024);
+    save(app)
+}
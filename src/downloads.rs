@@ -0,0 +1,425 @@
+use iced::widget::{button, column, container, progress_bar, row, scrollable, text};
+use iced::{Alignment, Element, Length, Task};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use crate::bootstrap::*;
+use crate::cache::{self, CacheEntry};
+use crate::error::Error;
+use crate::helpers;
+use crate::library;
+use crate::request::{self, DownloadProgress};
+use crate::search::visible_samples;
+use crate::status::StatusBarLevel;
+use crate::{themes, AudioCloud, Message, Sample, SampleType, ICON_FONT};
+
+const MAX_CONCURRENT_DOWNLOADS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    Progress(usize, DownloadProgress),
+    Cancel(usize),
+    ClearFinished,
+    TogglePanel,
+    Batch(BatchSource),
+    PackListed(String, Result<Vec<Sample>, Error>),
+}
+
+#[derive(Debug, Clone)]
+pub enum BatchSource {
+    Results,
+    Favourites,
+    Pack(String),
+}
+
+struct Batch {
+    id: usize,
+    label: String,
+    total: usize,
+    succeeded: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadStatus {
+    Queued,
+    Running,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+pub struct DownloadEntry {
+    pub id: usize,
+    pub server: String,
+    pub path: String,
+    pub name: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+    pub status: DownloadStatus,
+    sampletype: SampleType,
+    cancel: Option<Arc<Notify>>,
+    batch: Option<usize>,
+}
+impl DownloadEntry {
+    pub fn progress(&self) -> f32 {
+        match self.total {
+            Some(total) if total > 0 => self.downloaded as f32 / total as f32,
+            _ => 0.0,
+        }
+    }
+    pub fn is_finished(&self) -> bool {
+        !matches!(
+            self.status,
+            DownloadStatus::Queued | DownloadStatus::Running
+        )
+    }
+}
+
+pub struct DownloadManager {
+    pub entries: Vec<DownloadEntry>,
+    pub show_panel: bool,
+    queue: VecDeque<usize>,
+    batches: Vec<Batch>,
+    next_id: usize,
+}
+
+impl DownloadManager {
+    pub fn new() -> Self {
+        DownloadManager {
+            entries: vec![],
+            show_panel: false,
+            queue: VecDeque::new(),
+            batches: vec![],
+            next_id: 0,
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.status == DownloadStatus::Running)
+            .count()
+    }
+
+    pub fn active(&self) -> usize {
+        self.entries.iter().filter(|e| !e.is_finished()).count()
+    }
+
+    pub fn is_pending(&self, server_url: &str, path: &str) -> bool {
+        let server = helpers::server_id(server_url);
+        self.entries
+            .iter()
+            .any(|e| helpers::server_id(&e.server) == server && e.path == path && !e.is_finished())
+    }
+
+    fn entry_mut(&mut self, id: usize) -> Option<&mut DownloadEntry> {
+        self.entries.iter_mut().find(|e| e.id == id)
+    }
+}
+
+pub fn enqueue(app: &mut AudioCloud, sample: Sample) -> Task<Message> {
+    if app
+        .downloads
+        .is_pending(&app.settings.server_url, &sample.path)
+    {
+        return Task::none();
+    }
+    push_entry(app, sample, None);
+    start_queued(app)
+}
+
+fn push_entry(app: &mut AudioCloud, sample: Sample, batch: Option<usize>) {
+    let id = app.downloads.next_id;
+    app.downloads.next_id += 1;
+    let path = sample.path;
+    let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+    app.downloads.entries.push(DownloadEntry {
+        id,
+        server: app.settings.server_url.clone(),
+        path,
+        name,
+        sampletype: sample.sampletype,
+        downloaded: 0,
+        total: None,
+        status: DownloadStatus::Queued,
+        cancel: None,
+        batch,
+    });
+    app.downloads.queue.push_back(id);
+}
+
+// Queues every sample that isn't downloaded or already in flight
+pub fn enqueue_batch(app: &mut AudioCloud, label: &str, samples: Vec<Sample>) -> Task<Message> {
+    let (skipped, samples): (Vec<Sample>, Vec<Sample>) = samples.into_iter().partition(|s| {
+        app.cache.contains(&app.settings.server_url, &s.path)
+            || library::is_library_path(&s.path)
+            || app.downloads.is_pending(&app.settings.server_url, &s.path)
+    });
+    if samples.is_empty() {
+        app.status.set(
+            StatusBarLevel::Neutral,
+            &format!("{}: nothing to download, {} skipped", label, skipped.len()),
+        );
+        return Task::none();
+    }
+    let batch_id = app.downloads.next_id;
+    app.downloads.next_id += 1;
+    app.downloads.batches.push(Batch {
+        id: batch_id,
+        label: label.to_string(),
+        total: samples.len(),
+        succeeded: 0,
+        failed: 0,
+        skipped: skipped.len(),
+    });
+    for sample in samples {
+        push_entry(app, sample, Some(batch_id));
+    }
+    app.status.set(
+        StatusBarLevel::Neutral,
+        &format!("{}: downloading...", label),
+    );
+    start_queued(app)
+}
+
+fn batch_finished(app: &mut AudioCloud, batch_id: usize, succeeded: bool) {
+    let batch = match app.downloads.batches.iter_mut().find(|b| b.id == batch_id) {
+        None => return,
+        Some(val) => val,
+    };
+    match succeeded {
+        true => batch.succeeded += 1,
+        false => batch.failed += 1,
+    }
+    let summary = format!(
+        "{}: {} downloaded, {} failed, {} skipped",
+        batch.label, batch.succeeded, batch.failed, batch.skipped
+    );
+    if batch.succeeded + batch.failed < batch.total {
+        app.status.set(
+            StatusBarLevel::Neutral,
+            &format!(
+                "{}: {}/{}",
+                batch.label,
+                batch.succeeded + batch.failed,
+                batch.total
+            ),
+        );
+        return;
+    }
+    let level = match batch.failed {
+        0 => StatusBarLevel::Succes,
+        _ => StatusBarLevel::Danger,
+    };
+    app.status.set(level, &summary);
+    app.downloads.batches.retain(|b| b.id != batch_id);
+}
+
+fn start_queued(app: &mut AudioCloud) -> Task<Message> {
+    let mut tasks = vec![];
+    while app.downloads.running() < MAX_CONCURRENT_DOWNLOADS {
+        let id = match app.downloads.queue.pop_front() {
+            None => break,
+            Some(val) => val,
+        };
+        let api = app.api.clone();
+        let entry = match app.downloads.entry_mut(id) {
+            Some(entry) if entry.status == DownloadStatus::Queued => entry,
+            _ => continue,
+        };
+        let server_url = entry.server.clone();
+        let cancel = Arc::new(Notify::new());
+        let task = Task::run(
+            api.download_stream(server_url, entry.path.clone(), cancel.clone()),
+            move |p| Message::Download(DownloadEvent::Progress(id, p)),
+        );
+        entry.status = DownloadStatus::Running;
+        entry.cancel = Some(cancel);
+        tasks.push(task);
+    }
+    Task::batch(tasks)
+}
+
+pub fn download_event(app: &mut AudioCloud, event: DownloadEvent) -> Task<Message> {
+    match event {
+        DownloadEvent::Progress(id, progress) => {
+            let entry = match app.downloads.entry_mut(id) {
+                None => return Task::none(),
+                Some(val) => val,
+            };
+            match progress {
+                DownloadProgress::Progress(downloaded, total) => {
+                    entry.downloaded = downloaded;
+                    entry.total = total;
+                }
+                DownloadProgress::Finished(_) if entry.status == DownloadStatus::Cancelled => {
+                    return start_queued(app);
+                }
+                DownloadProgress::Finished(res) => {
+                    entry.cancel = None;
+                    let sampletype = entry.sampletype.clone();
+                    let res = res.map(|cache_entry| CacheEntry {
+                        sampletype: Some(sampletype),
+                        ..cache_entry
+                    });
+                    entry.status = match &res {
+                        Ok(_) => DownloadStatus::Done,
+                        Err(e) => DownloadStatus::Failed(e.to_string()),
+                    };
+                    // Batches report one summary instead of a status per sample
+                    if let Some(batch_id) = entry.batch {
+                        batch_finished(app, batch_id, res.is_ok());
+                        let stored = match res {
+                            Ok(cache_entry) => cache::store(app, cache_entry),
+                            Err(_) => Task::none(),
+                        };
+                        return Task::batch([stored, start_queued(app)]);
+                    }
+                    return Task::batch([
+                        Task::perform(request::nothing(), move |()| {
+                            Message::SampleAudioDownloaded(res)
+                        }),
+                        start_queued(app),
+                    ]);
+                }
+            }
+        }
+        DownloadEvent::Cancel(id) => {
+            if let Some(entry) = app.downloads.entry_mut(id) {
+                if entry.is_finished() {
+                    return Task::none();
+                }
+                if let Some(cancel) = entry.cancel.take() {
+                    cancel.notify_one();
+                }
+                entry.status = DownloadStatus::Cancelled;
+                match entry.batch {
+                    Some(batch_id) => batch_finished(app, batch_id, false),
+                    None => app
+                        .status
+                        .set(StatusBarLevel::Neutral, "Download cancelled"),
+                }
+            }
+            app.downloads.queue.retain(|queued| *queued != id);
+            return start_queued(app);
+        }
+        DownloadEvent::ClearFinished => {
+            app.downloads.entries.retain(|e| !e.is_finished());
+        }
+        DownloadEvent::TogglePanel => {
+            app.downloads.show_panel = !app.downloads.show_panel;
+        }
+        DownloadEvent::Batch(source) => match source {
+            BatchSource::Results => {
+                let samples = visible_samples(app).into_iter().cloned().collect();
+                return enqueue_batch(app, "Results", samples);
+            }
+            BatchSource::Favourites => {
+                let samples = app.settings.favourite_samples.clone();
+                return enqueue_batch(app, "Favourites", samples);
+            }
+            BatchSource::Pack(id) => {
+                app.status
+                    .set(StatusBarLevel::Neutral, "Listing pack samples...");
+                return Task::perform(
+                    app.api
+                        .clone()
+                        .get_pack_samples(id.clone(), app.settings.server_url.clone()),
+                    move |res| Message::Download(DownloadEvent::PackListed(id.clone(), res)),
+                );
+            }
+        },
+        DownloadEvent::PackListed(id, res) => match res {
+            Err(e) => app.status.set(
+                StatusBarLevel::Danger,
+                &format!("Failed to list pack: {}", e),
+            ),
+            Ok(samples) => {
+                let name = match app.pack_meta.iter().find(|p| p.id == id) {
+                    Some(pack) => pack.name.clone(),
+                    None => id,
+                };
+                if samples.is_empty() {
+                    app.status.set(
+                        StatusBarLevel::Danger,
+                        &format!("{}: the server listed no samples", name),
+                    );
+                    return Task::none();
+                }
+                return enqueue_batch(app, &name, samples);
+            }
+        },
+    }
+    Task::none()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let mb = bytes as f32 / (1024.0 * 1024.0);
+    if mb >= 1.0 {
+        format!("{:.1} MB", mb)
+    } else {
+        format!("{:.0} KB", bytes as f32 / 1024.0)
+    }
+}
+
+pub fn downloads_panel(app: &AudioCloud) -> Element<Message> {
+    let mut list = column![].spacing(10);
+    if app.downloads.entries.is_empty() {
+        list = list.push(text("No downloads"));
+    }
+    for entry in app.downloads.entries.iter().rev() {
+        let size = match entry.total {
+            Some(total) => format!(
+                "{} / {}",
+                format_bytes(entry.downloaded),
+                format_bytes(total)
+            ),
+            None => format_bytes(entry.downloaded),
+        };
+        let status = match &entry.status {
+            DownloadStatus::Queued => text("Queued"),
+            DownloadStatus::Running => text(size),
+            DownloadStatus::Done => text("Done").style(text::success),
+            DownloadStatus::Failed(e) => text(e.clone()).style(text::danger),
+            DownloadStatus::Cancelled => text("Cancelled").style(themes::text_fg),
+        };
+        let mut entry_row = row![column![
+            text(entry.name.clone()).size(14),
+            progress_bar(0.0..=1.0, entry.progress()).height(Length::Fixed(6.0)),
+            status.size(12),
+        ]
+        .spacing(4)
+        .width(Length::Fill)]
+        .spacing(10)
+        .align_y(Alignment::Center);
+        if !entry.is_finished() {
+            entry_row = entry_row.push(
+                button(text(icon_to_string(Bootstrap::XCircle)).font(ICON_FONT))
+                    .style(button::text)
+                    .on_press(Message::Download(DownloadEvent::Cancel(entry.id))),
+            );
+        }
+        list = list.push(entry_row);
+    }
+
+    let clear = button(text("Clear finished"))
+        .style(button::text)
+        .on_press(Message::Download(DownloadEvent::ClearFinished));
+
+    container(
+        column![
+            text("Downloads").size(20),
+            scrollable(list).height(Length::Shrink),
+            clear
+        ]
+        .spacing(10),
+    )
+    .style(container::rounded_box)
+    .width(Length::Fixed(320.0))
+    .max_height(400.0)
+    .padding(10)
+    .into()
+}
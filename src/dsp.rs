@@ -0,0 +1,598 @@
+use std::f32::consts::PI;
+
+use crate::audio;
+
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+const SHELF_Q: f32 = 0.707;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    HighPass,
+    LowPass,
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+impl std::fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterKind::HighPass => write!(f, "Highpass"),
+            FilterKind::LowPass => write!(f, "Lowpass"),
+            FilterKind::Peaking => write!(f, "Peak"),
+            FilterKind::LowShelf => write!(f, "Low shelf"),
+            FilterKind::HighShelf => write!(f, "High shelf"),
+        }
+    }
+}
+
+// Second order filter from the RBJ audio EQ cookbook, normalized so a0 is 1
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+impl Biquad {
+    fn new(kind: FilterKind, freq: f32, q: f32, gain_db: f32, sample_rate: u32) -> Self {
+        let freq = freq.clamp(MIN_FREQ, sample_rate as f32 * 0.49);
+        let w0 = 2.0 * PI * freq / sample_rate as f32;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a = 10f32.powf(gain_db / 40.0);
+        let sq = 2.0 * a.sqrt() * alpha;
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            FilterKind::LowPass => (
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            FilterKind::HighPass => (
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            FilterKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            FilterKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + sq),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - sq),
+                (a + 1.0) + (a - 1.0) * cos + sq,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - sq,
+            ),
+            FilterKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + sq),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sq),
+                (a + 1.0) - (a - 1.0) * cos + sq,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - sq,
+            ),
+        };
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    // Interleaved audio, every channel keeps its own filter state
+    fn process(&self, samples: &mut [f32], channels: usize) {
+        let mut state = vec![[0.0f32; 4]; channels];
+        for frame in samples.chunks_mut(channels) {
+            for (sample, s) in frame.iter_mut().zip(state.iter_mut()) {
+                let [x1, x2, y1, y2] = *s;
+                let x = *sample;
+                let y = self.b0 * x + self.b1 * x1 + self.b2 * x2 - self.a1 * y1 - self.a2 * y2;
+                *s = [x, x1, y, y1];
+                *sample = y;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Band {
+    pub kind: FilterKind,
+    pub freq: f32,
+    pub q: f32,
+    pub gain: f32,
+}
+
+// Highpass and lowpass are off while their cutoff is None, bands with no gain are skipped
+#[derive(Debug, Clone, PartialEq)]
+pub struct Equalizer {
+    pub highpass: Option<f32>,
+    pub highpass_q: f32,
+    pub lowpass: Option<f32>,
+    pub lowpass_q: f32,
+    pub bands: Vec<Band>,
+}
+impl Default for Equalizer {
+    fn default() -> Self {
+        let band = |kind: FilterKind, freq: f32| Band {
+            kind,
+            freq,
+            q: 1.0,
+            gain: 0.0,
+        };
+        Equalizer {
+            highpass: None,
+            highpass_q: 0.707,
+            lowpass: None,
+            lowpass_q: 0.707,
+            bands: vec![
+                band(FilterKind::LowShelf, 120.0),
+                band(FilterKind::Peaking, 1000.0),
+                band(FilterKind::HighShelf, 8000.0),
+            ],
+        }
+    }
+}
+impl Equalizer {
+    fn filters(&self, sample_rate: u32) -> Vec<Biquad> {
+        let mut filters = vec![];
+        if let Some(freq) = self.highpass {
+            filters.push(Biquad::new(
+                FilterKind::HighPass,
+                freq,
+                self.highpass_q,
+                0.0,
+                sample_rate,
+            ));
+        }
+        if let Some(freq) = self.lowpass {
+            filters.push(Biquad::new(
+                FilterKind::LowPass,
+                freq,
+                self.lowpass_q,
+                0.0,
+                sample_rate,
+            ));
+        }
+        for band in self.bands.iter().filter(|b| b.gain.abs() > 0.01) {
+            let q = match band.kind {
+                FilterKind::LowShelf | FilterKind::HighShelf => SHELF_Q,
+                _ => band.q,
+            };
+            filters.push(Biquad::new(band.kind, band.freq, q, band.gain, sample_rate));
+        }
+        filters
+    }
+
+    pub fn apply(&self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        let channels = (channels as usize).max(1);
+        for filter in self.filters(sample_rate) {
+            filter.process(samples, channels);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FadeCurve {
+    Linear,
+    Exponential,
+}
+impl FadeCurve {
+    pub fn all() -> Vec<Self> {
+        vec![FadeCurve::Linear, FadeCurve::Exponential]
+    }
+    // Exponential fades are linear in dB, from -60 dB up to full level
+    fn gain(&self, t: f32) -> f32 {
+        match self {
+            _ if t <= 0.0 => 0.0,
+            FadeCurve::Linear => t,
+            FadeCurve::Exponential => 10f32.powf(3.0 * (t - 1.0)),
+        }
+    }
+}
+impl std::fmt::Display for FadeCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FadeCurve::Linear => write!(f, "Linear"),
+            FadeCurve::Exponential => write!(f, "Exponential"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalize {
+    Off,
+    Peak,
+    Loudness,
+}
+impl Normalize {
+    pub fn all() -> Vec<Self> {
+        vec![Normalize::Off, Normalize::Peak, Normalize::Loudness]
+    }
+}
+impl std::fmt::Display for Normalize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Normalize::Off => write!(f, "No normalizing"),
+            Normalize::Peak => write!(f, "Peak"),
+            Normalize::Loudness => write!(f, "Loudness (LUFS)"),
+        }
+    }
+}
+
+// The trim only moves fades, measuring and export, the audio around it is kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edits {
+    pub trim: (f32, f32),
+    pub fade_in: u32,
+    pub fade_out: u32,
+    pub fade_curve: FadeCurve,
+    pub normalize: Normalize,
+    pub peak_target: f32,
+    pub loudness_target: f32,
+}
+impl Default for Edits {
+    fn default() -> Self {
+        Edits {
+            trim: (0.0, 1.0),
+            fade_in: 0,
+            fade_out: 0,
+            fade_curve: FadeCurve::Linear,
+            normalize: Normalize::Off,
+            peak_target: -0.1,
+            loudness_target: -14.0,
+        }
+    }
+}
+impl Edits {
+    pub fn apply(&self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        let (start, end) = sample_bounds(self.trim, samples.len(), channels);
+        let channels = (channels as usize).max(1);
+        let region = &mut samples[start..end];
+        let ms_to_frames = |ms: u32| (ms as u64 * sample_rate as u64 / 1000) as usize;
+        fade(
+            region,
+            channels,
+            ms_to_frames(self.fade_in),
+            self.fade_curve,
+            true,
+        );
+        fade(
+            region,
+            channels,
+            ms_to_frames(self.fade_out),
+            self.fade_curve,
+            false,
+        );
+
+        let peak = peak(region);
+        if peak <= 0.0 {
+            return;
+        }
+        let gain = match self.normalize {
+            Normalize::Off => return,
+            Normalize::Peak => db_to_gain(self.peak_target) / peak,
+            // Loud targets would clip short transients, so the gain stops at full scale
+            Normalize::Loudness => match loudness(region, channels as u16, sample_rate) {
+                None => return,
+                Some(lufs) => db_to_gain(self.loudness_target - lufs).min(1.0 / peak),
+            },
+        };
+        samples.iter_mut().for_each(|s| *s *= gain);
+    }
+}
+
+// Sample indices of a range from 0.0 to 1.0 like the trim, on frame boundaries
+pub fn sample_bounds(range: (f32, f32), len: usize, channels: u16) -> (usize, usize) {
+    let start = audio::frame_index(range.0, len, channels);
+    let end = audio::frame_index(range.1, len, channels);
+    match end > start {
+        true => (start, end),
+        false => (0, len),
+    }
+}
+
+fn fade(region: &mut [f32], channels: usize, frames: usize, curve: FadeCurve, fade_in: bool) {
+    let total = region.len() / channels;
+    let frames = frames.min(total);
+    for i in 0..frames {
+        let gain = curve.gain(i as f32 / frames as f32);
+        let frame = match fade_in {
+            true => i,
+            false => total - 1 - i,
+        };
+        for sample in &mut region[frame * channels..(frame + 1) * channels] {
+            *sample *= gain;
+        }
+    }
+}
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+pub fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0, |max, s| s.abs().max(max))
+}
+
+// Integrated loudness after ITU-R BS.1770, None for silence
+pub fn loudness(samples: &[f32], channels: u16, sample_rate: u32) -> Option<f32> {
+    let channels = (channels as usize).max(1);
+    let mut weighted = samples.to_vec();
+    Biquad::new(FilterKind::HighShelf, 1681.97, 0.7072, 4.0, sample_rate)
+        .process(&mut weighted, channels);
+    Biquad::new(FilterKind::HighPass, 38.14, 0.5, 0.0, sample_rate)
+        .process(&mut weighted, channels);
+
+    // Channel energies add up, so the sum is divided by frames rather than samples
+    let energy = |block: &[f32]| {
+        block.iter().map(|s| s * s).sum::<f32>() * channels as f32 / block.len().max(1) as f32
+    };
+    let block = sample_rate as usize * 4 / 10 * channels;
+    let step = (block / 4 / channels).max(1) * channels;
+    let energies: Vec<f32> = match weighted.len() <= block {
+        true => vec![energy(&weighted)],
+        false => (0..=(weighted.len() - block) / step)
+            .map(|i| energy(&weighted[i * step..i * step + block]))
+            .collect(),
+    };
+
+    let lufs = |e: f32| -0.691 + 10.0 * e.log10();
+    let mean = |e: &[f32]| e.iter().sum::<f32>() / e.len() as f32;
+    let gated: Vec<f32> = energies.into_iter().filter(|e| lufs(*e) > -70.0).collect();
+    if gated.is_empty() {
+        return None;
+    }
+    let relative = lufs(mean(&gated)) - 10.0;
+    let gated: Vec<f32> = gated.into_iter().filter(|e| lufs(*e) > relative).collect();
+    Some(lufs(mean(&gated)))
+}
+
+pub fn reverse(samples: &mut [f32], channels: u16) {
+    let channels = (channels as usize).max(1);
+    samples.reverse();
+    samples
+        .chunks_mut(channels)
+        .for_each(|frame| frame.reverse());
+}
+
+pub fn apply_gain(samples: &mut [f32], db: f32) {
+    let gain = db_to_gain(db);
+    samples.iter_mut().for_each(|s| *s *= gain);
+}
+
+// The length changes with the pitch like on a sampler
+pub fn resample(samples: &[f32], channels: u16, semitones: f32) -> Vec<f32> {
+    let channels = (channels as usize).max(1);
+    let ratio = 2f64.powf(semitones as f64 / 12.0);
+    if ratio <= 1.0 {
+        return interpolate(samples, channels, ratio);
+    }
+    let mut filtered = samples.to_vec();
+    anti_alias(&mut filtered, channels, ratio);
+    interpolate(&filtered, channels, ratio)
+}
+
+// Butterworth lowpass against aliasing, only the cutoff relative to the rate matters
+fn anti_alias(samples: &mut [f32], channels: usize, ratio: f64) {
+    let rate = 48000;
+    let cutoff = (0.45 / ratio) as f32 * rate as f32;
+    for q in [0.5412, 1.3066] {
+        Biquad::new(FilterKind::LowPass, cutoff, q, 0.0, rate).process(samples, channels);
+    }
+}
+
+fn interpolate(samples: &[f32], channels: usize, ratio: f64) -> Vec<f32> {
+    let frames = samples.len() / channels;
+    if frames == 0 {
+        return vec![];
+    }
+    let out_frames = (frames as f64 / ratio) as usize;
+    let mut out = Vec::with_capacity(out_frames * channels);
+    for i in 0..out_frames {
+        let pos = i as f64 * ratio;
+        let frame = (pos as usize).min(frames - 1);
+        let next = (frame + 1).min(frames - 1);
+        let frac = (pos - frame as f64) as f32;
+        for c in 0..channels {
+            let a = samples[frame * channels + c];
+            let b = samples[next * channels + c];
+            out.push(a + (b - a) * frac);
+        }
+    }
+    out
+}
+
+pub fn freq_from_position(pos: f32) -> f32 {
+    MIN_FREQ * (MAX_FREQ / MIN_FREQ).powf(pos.clamp(0.0, 1.0))
+}
+
+pub fn position_from_freq(freq: f32) -> f32 {
+    (freq / MIN_FREQ).ln() / (MAX_FREQ / MIN_FREQ).ln()
+}
+
+pub fn format_freq(freq: f32) -> String {
+    match freq >= 1000.0 {
+        true => format!("{:.1} kHz", freq / 1000.0),
+        false => format!("{:.0} Hz", freq),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    fn sine(freq: f32, seconds: f32, amplitude: f32) -> Vec<f32> {
+        let len = (RATE as f32 * seconds) as usize;
+        (0..len)
+            .map(|i| amplitude * (2.0 * PI * freq * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn bounds_stay_on_frames() {
+        assert_eq!(sample_bounds((0.0, 1.0), 100, 2), (0, 100));
+        assert_eq!(sample_bounds((0.25, 0.75), 100, 2), (24, 74));
+        assert_eq!(sample_bounds((0.5, 0.5), 100, 2), (0, 100));
+        assert_eq!(sample_bounds((0.9, 0.1), 10, 1), (0, 10));
+    }
+
+    #[test]
+    fn peak_normalizing() {
+        let mut samples = sine(440.0, 0.5, 0.25);
+        let edits = Edits {
+            normalize: Normalize::Peak,
+            peak_target: -6.0,
+            ..Edits::default()
+        };
+        edits.apply(&mut samples, 1, RATE);
+        assert!((peak(&samples) - db_to_gain(-6.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn loudness_of_a_full_scale_sine() {
+        // A 997 Hz sine at full scale reads about -3 LUFS on one channel
+        let lufs = loudness(&sine(997.0, 2.0, 1.0), 1, RATE).unwrap();
+        assert!((lufs + 3.01).abs() < 0.2, "{}", lufs);
+        assert!(loudness(&[0.0; RATE as usize], 1, RATE).is_none());
+    }
+
+    #[test]
+    fn loudness_normalizing_stops_at_full_scale() {
+        let edits = Edits {
+            normalize: Normalize::Loudness,
+            loudness_target: -20.0,
+            ..Edits::default()
+        };
+        let mut samples = sine(997.0, 2.0, 0.1);
+        edits.apply(&mut samples, 1, RATE);
+        let lufs = loudness(&samples, 1, RATE).unwrap();
+        assert!((lufs + 20.0).abs() < 0.1, "{}", lufs);
+
+        let loud = Edits {
+            loudness_target: 0.0,
+            ..edits
+        };
+        let mut samples = sine(997.0, 2.0, 0.1);
+        loud.apply(&mut samples, 1, RATE);
+        assert!(peak(&samples) <= 1.0 + 1e-4);
+    }
+
+    #[test]
+    fn fades_start_and_end_silent() {
+        let mut samples = vec![1.0; 2 * RATE as usize];
+        let edits = Edits {
+            fade_in: 100,
+            fade_out: 100,
+            ..Edits::default()
+        };
+        edits.apply(&mut samples, 2, RATE);
+        assert_eq!(samples[0], 0.0);
+        assert_eq!(samples[1], 0.0);
+        assert!(samples[samples.len() - 1] < 0.01);
+        assert_eq!(samples[RATE as usize], 1.0);
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn frequency_positions_round_trip() {
+        assert!((freq_from_position(0.0) - MIN_FREQ).abs() < 1e-3);
+        assert!((freq_from_position(1.0) - MAX_FREQ).abs() < 1e-1);
+        for freq in [20.0, 80.0, 1000.0, 12000.0, 20000.0] {
+            let back = freq_from_position(position_from_freq(freq));
+            assert!((back - freq).abs() / freq < 1e-4, "{} {}", freq, back);
+        }
+    }
+
+    #[test]
+    fn lowpass_attenuates_above_cutoff() {
+        let eq = Equalizer {
+            lowpass: Some(500.0),
+            ..Equalizer::default()
+        };
+        let mut high = sine(5000.0, 0.5, 0.5);
+        let mut low = sine(100.0, 0.5, 0.5);
+        eq.apply(&mut high, 1, RATE);
+        eq.apply(&mut low, 1, RATE);
+        // Skip the settling of the filter
+        let skip = RATE as usize / 10;
+        assert!(rms(&high[skip..]) < rms(&sine(5000.0, 0.5, 0.5)[skip..]) * 0.05);
+        assert!(rms(&low[skip..]) > rms(&sine(100.0, 0.5, 0.5)[skip..]) * 0.9);
+    }
+
+    #[test]
+    fn flat_equalizer_changes_nothing() {
+        let original = sine(440.0, 0.1, 0.5);
+        let mut samples = original.clone();
+        Equalizer::default().apply(&mut samples, 1, RATE);
+        assert_eq!(samples, original);
+    }
+
+    #[test]
+    fn peak_band_boosts_its_frequency() {
+        let mut eq = Equalizer::default();
+        eq.bands[1].gain = 6.0;
+        let freq = eq.bands[1].freq;
+        let mut samples = sine(freq, 0.5, 0.25);
+        eq.apply(&mut samples, 1, RATE);
+        let skip = RATE as usize / 10;
+        let gain = rms(&samples[skip..]) / rms(&sine(freq, 0.5, 0.25)[skip..]);
+        assert!((gain - db_to_gain(6.0)).abs() < 0.05, "{}", gain);
+    }
+
+    #[test]
+    fn reverse_keeps_channel_order() {
+        let mut samples = [1.0, -1.0, 2.0, -2.0, 3.0, -3.0];
+        reverse(&mut samples, 2);
+        assert_eq!(samples, [3.0, -3.0, 2.0, -2.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn resample_changes_length_with_pitch() {
+        let samples = [0.0; 2 * 1000];
+        assert_eq!(resample(&samples, 2, 0.0).len(), 2000);
+        assert_eq!(resample(&samples, 2, 12.0).len(), 1000);
+        assert_eq!(resample(&samples, 2, -12.0).len(), 4000);
+        assert!(resample(&[], 2, 3.0).is_empty());
+    }
+
+    #[test]
+    fn resample_keeps_channels_apart() {
+        let samples: Vec<f32> = (0..1000).flat_map(|_| [1.0, -1.0]).collect();
+        let down = resample(&samples, 2, -7.0);
+        assert!(down.chunks(2).all(|frame| frame == [1.0, -1.0]));
+        // Up runs through the lowpass, which needs a moment to settle
+        let up = resample(&samples, 2, 7.0);
+        assert!(up[200..]
+            .chunks(2)
+            .all(|frame| (frame[0] - 1.0).abs() < 1e-3 && (frame[1] + 1.0).abs() < 1e-3));
+    }
+
+    #[test]
+    fn resample_up_filters_what_would_alias() {
+        let skip = RATE as usize / 20;
+        let high = resample(&sine(22000.0, 0.5, 0.5), 1, 12.0);
+        assert!(rms(&high[skip..]) < rms(&sine(22000.0, 0.5, 0.5)) * 0.1);
+        let low = resample(&sine(1000.0, 0.5, 0.5), 1, 12.0);
+        assert!(rms(&low[skip..]) > rms(&sine(1000.0, 0.5, 0.5)) * 0.9);
+    }
+}
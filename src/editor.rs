@@ -1,25 +1,51 @@
+use crate::audio;
 use crate::bootstrap::*;
+use crate::cache::{self, CacheEntry};
+use crate::dsp::{self, Edits, Equalizer, FadeCurve, FilterKind, Normalize};
+use crate::error::{Error, ErrorType};
+use crate::helpers;
+use crate::operations::{self, EditHistory, Operation};
 use crate::themes;
 use crate::waveform::*;
-use crate::{AudioCloud, Message, ViewControl, ARRAYLEN, ICON_FONT};
+use crate::{AudioCloud, Message, StatusBarLevel, ViewControl, ARRAYLEN, ICON_FONT};
 use audiocloud_lib::*;
-use rodio::{source::Source, Decoder, OutputStream};
+use rodio::{source::Source, Decoder, OutputStream, Sink};
 
 use iced::widget::{
-    button, checkbox, column, combo_box, container, horizontal_space, row, scrollable, stack, text,
-    text_input, tooltip, vertical_space,
+    button, checkbox, column, combo_box, container, horizontal_space, pick_list, row, scrollable,
+    slider, stack, text, text_input, tooltip, vertical_space,
 };
 use iced::Element;
-use iced::{Alignment, Padding, Subscription, Task, Theme};
+use iced::{Alignment, Length, Padding, Subscription, Task, Theme};
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use xxhash_rust::xxh3::xxh3_64;
 
+const DEFAULT_HIGHPASS: f32 = 80.0;
+const DEFAULT_LOWPASS: f32 = 12000.0;
+
+// original stays as decoded for B, base takes the operations that fell out of the history
+// and audio is the history replayed over base
 pub struct Editor {
     pub sample: Sample,
     pub audio: Arc<RwLock<Vec<f32>>>,
+    pub original: Arc<RwLock<Vec<f32>>>,
+    base: Arc<RwLock<Vec<f32>>>,
+    pub channels: u16,
+    pub sample_rate: u32,
     pub wav: [f32; ARRAYLEN as usize],
-    pub highpass: Option<f32>,
-    pub lowpass: Option<f32>,
+    pub eq: Equalizer,
+    pub edits: Edits,
+    pub exported: Option<String>,
+    pub history: EditHistory,
+    pub transform: Transform,
+    pub bypass: Arc<AtomicBool>,
+    pub sink: Option<Sink>,
+    pub transport: Arc<audio::Transport>,
+    render_generation: u64,
 }
 
 impl Editor {
@@ -34,37 +60,500 @@ impl Editor {
                 sampletype: SampleType::OneShot,
             },
             audio: Arc::new(RwLock::new(vec![])),
+            original: Arc::new(RwLock::new(vec![])),
+            base: Arc::new(RwLock::new(vec![])),
+            channels: 0,
+            sample_rate: 0,
             wav: [0.0; ARRAYLEN as usize],
-            highpass: None,
-            lowpass: None,
+            eq: Equalizer::default(),
+            edits: Edits::default(),
+            exported: None,
+            history: EditHistory::default(),
+            transform: Transform::default(),
+            bypass: Arc::new(AtomicBool::new(false)),
+            sink: None,
+            transport: Arc::new(audio::Transport::new()),
+            render_generation: 0,
+        }
+    }
+    pub fn reset(&mut self) {
+        self.sink = None;
+        self.eq = Equalizer::default();
+        self.edits = Edits::default();
+        self.exported = None;
+        self.history = EditHistory::default();
+        self.transform = Transform::default();
+        self.bypass.store(false, Ordering::Relaxed);
+        self.transport = Arc::new(audio::Transport::new());
+    }
+    fn len(&self) -> usize {
+        self.audio.read().map(|a| a.len()).unwrap_or(0)
+    }
+    pub fn is_playing(&self) -> bool {
+        self.sink.as_ref().map(|s| !s.is_paused()).unwrap_or(false)
+    }
+    pub fn playhead(&self) -> Option<f32> {
+        match self.len() {
+            0 => None,
+            len => Some(self.transport.position.load(Ordering::Relaxed) as f32 / len as f32),
+        }
+    }
+    fn looping(&self) -> bool {
+        self.transport.looping.load(Ordering::Relaxed)
+    }
+    fn region(&self) -> (f32, f32) {
+        self.transport
+            .region
+            .read()
+            .map(|r| *r)
+            .unwrap_or((0.0, 1.0))
+    }
+    fn duration_of(&self, samples: usize) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
         }
+        let frames = samples / self.channels.max(1) as usize;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+    fn rewind(&self) {
+        let trim = self.edits.trim;
+        let start = match self.looping() {
+            true => self.region().0.clamp(trim.0, trim.1),
+            false => trim.0,
+        };
+        self.transport.seek(start, self.len(), self.channels);
+    }
+    fn set_trim(&mut self, trim: (f32, f32)) {
+        self.edits.trim = trim;
+        if let Ok(mut val) = self.transport.trim.write() {
+            *val = trim;
+        }
+    }
+    fn sync_settings(&mut self) {
+        let (eq, edits) = operations::settings(&self.history.ops);
+        self.eq = eq;
+        self.set_trim(edits.trim);
+        self.edits = edits;
+    }
+    fn ops(&self) -> Vec<Operation> {
+        let mut ops = self.history.ops.clone();
+        ops.extend(self.transform.ops());
+        ops
+    }
+    // Sample name with tags for its transformations, like "kick_rev_+3st.wav"
+    pub fn display_name(&self) -> String {
+        let tags = operations::name_tags(&self.ops());
+        if tags.is_empty() {
+            return self.sample.name.clone();
+        }
+        let path = Path::new(&self.sample.name);
+        let stem = match path.file_stem() {
+            Some(val) => val.to_string_lossy().to_string(),
+            None => self.sample.name.clone(),
+        };
+        let extension = match path.extension() {
+            Some(val) => val.to_string_lossy().to_string(),
+            None => String::from("wav"),
+        };
+        format!("{}_{}.{}", stem, tags.join("_"), extension)
+    }
+    fn push(&mut self, op: Operation) {
+        let baked = self.history.push(op);
+        operations::bake(&self.base, &baked, self.channels);
+    }
+    // Later operations were measured on the transformed audio
+    fn commit_transform(&mut self) {
+        for op in self.transform.ops() {
+            self.push(op);
+        }
+        self.transform = Transform::default();
     }
 }
 
-pub async fn load_editor_audio(audioref: Arc<RwLock<Vec<f32>>>) {
-    let file = BufReader::new(std::fs::File::open("editor.wav").expect("Couldnt open file"));
-    {
-        let mut write_audio = match audioref.write() {
-            Ok(val) => val,
-            Err(_) => {
-                return;
-            }
-        };
-        *write_audio = Decoder::new(file)
-            .expect("Couldnt parse audio")
-            .convert_samples()
-            .collect();
+pub async fn copy_to_editor(sample: Sample, file: PathBuf) -> Result<(Sample, String), Error> {
+    let tempaudio_path = "editor.wav";
+    if std::fs::copy(file, tempaudio_path).is_err() {
+        return Err(Error::new(ErrorType::FileSave));
+    }
+    Ok((sample, String::from(tempaudio_path)))
+}
+
+pub async fn load_editor_audio(audioref: Arc<RwLock<Vec<f32>>>) -> Option<(u16, u32)> {
+    let file = BufReader::new(std::fs::File::open("editor.wav").ok()?);
+    let decoder = Decoder::new(file).ok()?;
+    let format = (decoder.channels(), decoder.sample_rate());
+    let mut write_audio = audioref.write().ok()?;
+    *write_audio = decoder.convert_samples().collect();
+    Some(format)
+}
+
+#[derive(Debug, Clone)]
+pub enum EqChange {
+    Highpass(bool),
+    HighpassFreq(f32),
+    HighpassQ(f32),
+    Lowpass(bool),
+    LowpassFreq(f32),
+    LowpassQ(f32),
+    BandFreq(usize, f32),
+    BandGain(usize, f32),
+    BandQ(usize, f32),
+}
+
+// Transformations shown as a preview until they are committed to the history
+#[derive(Debug, Clone, Default)]
+pub struct Transform {
+    pub reverse: bool,
+    pub gain: f32,
+    pub semitones: i32,
+    pub cents: i32,
+}
+impl Transform {
+    fn is_empty(&self) -> bool {
+        self.ops().is_empty()
+    }
+    fn ops(&self) -> Vec<Operation> {
+        let mut ops = vec![];
+        if self.reverse {
+            ops.push(Operation::Reverse);
+        }
+        if self.gain != 0.0 {
+            ops.push(Operation::Gain(self.gain));
+        }
+        if self.semitones != 0 || self.cents != 0 {
+            ops.push(Operation::Pitch(
+                self.semitones as f32 + self.cents as f32 / 100.0,
+            ));
+        }
+        ops
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum TransformChange {
+    Reverse(bool),
+    Gain(f32),
+    Semitones(i32),
+    Cents(i32),
+}
+
+#[derive(Debug, Clone)]
+pub enum EditChange {
+    Trim(f32, f32),
+    ResetTrim,
+    FadeIn(u32),
+    FadeOut(u32),
+    FadeCurve(FadeCurve),
+    Normalize(Normalize),
+    NormalizeTarget(f32),
+}
+
 #[derive(Debug, Clone)]
 pub enum EditorEvent {
-    PlaybackStart,
+    AudioLoaded(Option<(u16, u32)>),
+    TogglePlayback,
+    StopPlayback,
+    ToggleLoop,
+    SetLoopStart,
+    SetLoopEnd,
+    Seek(f32),
+    Tick(Instant),
+    ToggleBypass,
+    Eq(EqChange),
+    Edit(EditChange),
+    Apply,
+    Export,
+    Exported(Result<CacheEntry, Error>),
+    SilenceRegion,
+    Transform(TransformChange),
+    Preview,
+    CommitTransform,
+    DiscardTransform,
+    Undo,
+    Redo,
+    Revert,
+    Rendered(u64, Vec<f32>),
     ReloadWaveform,
     WaveformReloaded([f32; ARRAYLEN as usize]),
 }
+
+// Results of older settings are dropped
+fn render(app: &mut AudioCloud) -> Task<Message> {
+    let editor = &mut app.editor;
+    if editor.sample_rate == 0 {
+        return Task::none();
+    }
+    editor.render_generation += 1;
+    let generation = editor.render_generation;
+    Task::perform(
+        operations::render(
+            editor.base.clone(),
+            editor.channels,
+            editor.sample_rate,
+            editor.ops(),
+        ),
+        move |samples| Message::Editor(EditorEvent::Rendered(generation, samples)),
+    )
+}
+
+fn commit(app: &mut AudioCloud) -> Task<Message> {
+    let editor = &mut app.editor;
+    let (eq, edits) = operations::settings(&editor.history.ops);
+    if editor.eq != eq || editor.edits != edits {
+        editor.commit_transform();
+    }
+    if editor.eq != eq {
+        editor.push(Operation::Eq(editor.eq.clone()));
+    }
+    if editor.edits != edits {
+        editor.push(Operation::Edits(editor.edits.clone()));
+    }
+    render(app)
+}
+
+fn eq_changed(eq: &mut Equalizer, change: EqChange) {
+    match change {
+        EqChange::Highpass(on) => eq.highpass = on.then_some(DEFAULT_HIGHPASS),
+        EqChange::HighpassFreq(freq) => eq.highpass = Some(freq),
+        EqChange::HighpassQ(q) => eq.highpass_q = q,
+        EqChange::Lowpass(on) => eq.lowpass = on.then_some(DEFAULT_LOWPASS),
+        EqChange::LowpassFreq(freq) => eq.lowpass = Some(freq),
+        EqChange::LowpassQ(q) => eq.lowpass_q = q,
+        EqChange::BandFreq(i, freq) => {
+            if let Some(band) = eq.bands.get_mut(i) {
+                band.freq = freq;
+            }
+        }
+        EqChange::BandGain(i, gain) => {
+            if let Some(band) = eq.bands.get_mut(i) {
+                band.gain = gain;
+            }
+        }
+        EqChange::BandQ(i, q) => {
+            if let Some(band) = eq.bands.get_mut(i) {
+                band.q = q;
+            }
+        }
+    }
+}
+
+fn edit_changed(editor: &mut Editor, change: EditChange) -> bool {
+    match change {
+        EditChange::Trim(start, end) => {
+            editor.set_trim((start, end));
+            return false;
+        }
+        EditChange::ResetTrim => editor.set_trim((0.0, 1.0)),
+        EditChange::FadeIn(ms) => {
+            editor.edits.fade_in = ms;
+            return false;
+        }
+        EditChange::FadeOut(ms) => {
+            editor.edits.fade_out = ms;
+            return false;
+        }
+        EditChange::FadeCurve(curve) => editor.edits.fade_curve = curve,
+        EditChange::Normalize(mode) => editor.edits.normalize = mode,
+        EditChange::NormalizeTarget(target) => {
+            match editor.edits.normalize {
+                Normalize::Loudness => editor.edits.loudness_target = target,
+                _ => editor.edits.peak_target = target,
+            }
+            return false;
+        }
+    }
+    true
+}
+
+fn export_name(editor: &Editor) -> String {
+    let name = editor.display_name();
+    let stem = match Path::new(&name).file_stem() {
+        Some(val) => val.to_string_lossy().to_string(),
+        None => name.clone(),
+    };
+    match name == editor.sample.name {
+        true => stem + "_edit.wav",
+        false => stem + ".wav",
+    }
+}
+
+async fn export(
+    audio: Arc<RwLock<Vec<f32>>>,
+    channels: u16,
+    sample_rate: u32,
+    trim: (f32, f32),
+    server_url: String,
+    name: String,
+) -> Result<CacheEntry, Error> {
+    tokio::task::spawn_blocking(move || {
+        write_export(&audio, channels, sample_rate, trim, &server_url, name)
+    })
+    .await
+    .unwrap_or_else(|_| Err(Error::new(ErrorType::FileSave)))
+}
+
+// Written through a ".part" file like downloads
+fn write_export(
+    audio: &RwLock<Vec<f32>>,
+    channels: u16,
+    sample_rate: u32,
+    trim: (f32, f32),
+    server_url: &str,
+    name: String,
+) -> Result<CacheEntry, Error> {
+    let bytes = match audio.read() {
+        Err(_) => return Err(Error::new(ErrorType::FileSave)),
+        Ok(audio) => {
+            let (start, end) = dsp::sample_bounds(trim, audio.len(), channels);
+            audio::encode_wav(&audio[start..end], channels, sample_rate)
+        }
+    };
+    let content_hash = format!("{:016x}", xxh3_64(&bytes));
+    let file = content_hash.clone() + ".wav";
+    let path = cache::EXPORT_FOLDER.to_string() + &name;
+    let part_path = cache::part_path(server_url, &path);
+    if std::fs::write(&part_path, &bytes).is_err()
+        || std::fs::rename(&part_path, cache::CACHE_DIR.to_string() + &file).is_err()
+    {
+        let _ = std::fs::remove_file(&part_path);
+        return Err(Error::new(ErrorType::FileSave));
+    }
+    Ok(CacheEntry {
+        server: helpers::server_id(server_url),
+        path,
+        file,
+        original_name: name,
+        size: bytes.len() as u64,
+        content_hash,
+        last_access: cache::now(),
+        sampletype: None,
+    })
+}
+
 pub fn editor_event(app: &mut AudioCloud, event: EditorEvent) -> Task<Message> {
     match event {
+        EditorEvent::AudioLoaded(format) => match format {
+            None => app
+                .status
+                .set(StatusBarLevel::Danger, "Couldnt open editor audio"),
+            Some((channels, sample_rate)) => {
+                let editor = &mut app.editor;
+                editor.channels = channels;
+                editor.sample_rate = sample_rate;
+                if let (Ok(original), Ok(mut base)) = (editor.original.read(), editor.base.write())
+                {
+                    base.clone_from(&*original);
+                }
+                return render(app);
+            }
+        },
+        EditorEvent::Eq(change) => {
+            let toggled = matches!(change, EqChange::Highpass(_) | EqChange::Lowpass(_));
+            eq_changed(&mut app.editor.eq, change);
+            if toggled {
+                return commit(app);
+            }
+        }
+        EditorEvent::Edit(change) => {
+            if edit_changed(&mut app.editor, change) {
+                return commit(app);
+            }
+        }
+        EditorEvent::Apply => return commit(app),
+        EditorEvent::SilenceRegion => {
+            let (start, end) = app.editor.region();
+            app.editor.commit_transform();
+            app.editor.push(Operation::Silence(start, end));
+            return render(app);
+        }
+        EditorEvent::Transform(change) => {
+            let transform = &mut app.editor.transform;
+            match change {
+                TransformChange::Reverse(val) => {
+                    transform.reverse = val;
+                    return render(app);
+                }
+                TransformChange::Gain(val) => transform.gain = val,
+                TransformChange::Semitones(val) => transform.semitones = val,
+                TransformChange::Cents(val) => transform.cents = val,
+            }
+        }
+        EditorEvent::Preview => return render(app),
+        EditorEvent::CommitTransform => app.editor.commit_transform(),
+        EditorEvent::DiscardTransform => {
+            app.editor.transform = Transform::default();
+            return render(app);
+        }
+        // A pending transform is undone first
+        EditorEvent::Undo => {
+            if !app.editor.transform.is_empty() {
+                app.editor.transform = Transform::default();
+                return render(app);
+            }
+            if app.editor.history.undo() {
+                app.editor.sync_settings();
+                return render(app);
+            }
+        }
+        EditorEvent::Redo => {
+            if app.editor.history.redo() {
+                app.editor.sync_settings();
+                return render(app);
+            }
+        }
+        EditorEvent::Revert => {
+            let sample = app.editor.sample.clone();
+            return match app.sample_file(&sample.path) {
+                Some(file) => Task::perform(copy_to_editor(sample, file), Message::EditorSession),
+                None => Task::perform(
+                    app.api
+                        .clone()
+                        .get_editor_audio(sample, app.settings.server_url.clone()),
+                    Message::EditorSession,
+                ),
+            };
+        }
+        EditorEvent::Export => {
+            let editor = &app.editor;
+            if editor.sample_rate == 0 {
+                return Task::none();
+            }
+            return Task::perform(
+                export(
+                    editor.audio.clone(),
+                    editor.channels,
+                    editor.sample_rate,
+                    editor.edits.trim,
+                    app.settings.server_url.clone(),
+                    export_name(editor),
+                ),
+                |res| Message::Editor(EditorEvent::Exported(res)),
+            );
+        }
+        EditorEvent::Exported(res) => match res {
+            Err(e) => app.status.set(StatusBarLevel::Danger, &e.to_string()),
+            Ok(entry) => {
+                app.status.set(
+                    StatusBarLevel::Succes,
+                    &format!("Exported {}", entry.original_name),
+                );
+                app.editor.exported = Some(entry.path.clone());
+                return cache::store(app, entry);
+            }
+        },
+        EditorEvent::Rendered(generation, samples) => {
+            if generation != app.editor.render_generation {
+                return Task::none();
+            }
+            match app.editor.audio.write() {
+                Err(_) => return Task::none(),
+                Ok(mut audio) => *audio = samples,
+            }
+            return editor_event(app, EditorEvent::ReloadWaveform);
+        }
+        EditorEvent::ToggleBypass => {
+            app.editor.bypass.fetch_xor(true, Ordering::Relaxed);
+        }
         EditorEvent::ReloadWaveform => {
             return Task::perform(get_waveform_tk(app.editor.audio.clone()), |val| {
                 Message::Editor(EditorEvent::WaveformReloaded(val))
@@ -75,15 +564,328 @@ pub fn editor_event(app: &mut AudioCloud, event: EditorEvent) -> Task<Message> {
             app.status
                 .set(crate::StatusBarLevel::Succes, "Loaded Waveform");
         }
-        EditorEvent::PlaybackStart => {
-            //let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-            //let data = app.editor.audio.clone().read().unwrap().iter().copied();
-            //stream_handle.play_once(*data);
+        EditorEvent::TogglePlayback => match &app.editor.sink {
+            Some(sink) if sink.is_paused() => sink.play(),
+            Some(sink) => sink.pause(),
+            None => start_playback(app),
+        },
+        EditorEvent::StopPlayback => {
+            app.editor.sink = None;
+            app.editor.rewind();
+        }
+        EditorEvent::Tick(_) => {
+            if app.editor.sink.as_ref().map(|s| s.empty()).unwrap_or(false) {
+                app.editor.sink = None;
+                app.editor.rewind();
+            }
+        }
+        EditorEvent::Seek(pos) => {
+            let editor = &app.editor;
+            editor.transport.seek(pos, editor.len(), editor.channels);
         }
+        EditorEvent::ToggleLoop => {
+            app.editor
+                .transport
+                .looping
+                .fetch_xor(true, Ordering::Relaxed);
+        }
+        EditorEvent::SetLoopStart => set_loop_bound(&app.editor, true),
+        EditorEvent::SetLoopEnd => set_loop_bound(&app.editor, false),
     }
     Task::none()
 }
 
+fn set_loop_bound(editor: &Editor, start: bool) {
+    let pos = editor.playhead().unwrap_or(0.0);
+    if let Ok(mut region) = editor.transport.region.write() {
+        *region = match start {
+            true if pos < region.1 => (pos, region.1),
+            true => (pos, 1.0),
+            false if pos > region.0 => (region.0, pos),
+            false => (0.0, pos),
+        };
+    }
+}
+
+// Own sink, so the search player keeps its sample loaded
+pub fn start_playback(app: &mut AudioCloud) {
+    let devs = match &app.audio_devices {
+        None => {
+            app.status.set(StatusBarLevel::Danger, audio::NO_OUTPUT);
+            return;
+        }
+        Some(val) => val,
+    };
+    let sink = match Sink::try_new(&devs.stream_handle) {
+        Err(_) => {
+            app.status
+                .set(StatusBarLevel::Danger, "Couldnt start playback");
+            return;
+        }
+        Ok(val) => val,
+    };
+    devs.sink.pause();
+    app.player.is_playing = false;
+    let editor = &mut app.editor;
+    let (_, end) = editor.transport.trim_bounds(editor.len(), editor.channels);
+    if editor.transport.position.load(Ordering::Relaxed) >= end {
+        editor.rewind();
+    }
+    sink.set_volume(app.player.volume);
+    sink.append(audio::BufferSource::new(
+        editor.audio.clone(),
+        editor.original.clone(),
+        editor.bypass.clone(),
+        editor.transport.clone(),
+        editor.channels,
+        editor.sample_rate,
+    ));
+    editor.sink = Some(sink);
+}
+
+fn edit_message(change: EditChange) -> Message {
+    Message::Editor(EditorEvent::Edit(change))
+}
+
+fn fade_slider<'a>(label: &'a str, ms: u32, f: fn(u32) -> EditChange) -> Element<'a, Message> {
+    row![
+        text(label),
+        text(format!("{}ms", ms)).width(Length::Fixed(70.0)),
+        slider(std::ops::RangeInclusive::new(0, 2000), ms, move |ms| {
+            edit_message(f(ms))
+        })
+        .step(10u32)
+        .on_release(Message::Editor(EditorEvent::Apply))
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+fn edits_view(editor: &Editor) -> Element<Message> {
+    let edits = &editor.edits;
+    let fades = row![
+        fade_slider("Fade in", edits.fade_in, EditChange::FadeIn),
+        fade_slider("Fade out", edits.fade_out, EditChange::FadeOut),
+        pick_list(FadeCurve::all(), Some(edits.fade_curve), |curve| {
+            edit_message(EditChange::FadeCurve(curve))
+        }),
+    ]
+    .spacing(20)
+    .align_y(Alignment::Center);
+
+    let mut normalize = row![
+        text("Normalize").width(Length::Fixed(120.0)),
+        pick_list(Normalize::all(), Some(edits.normalize), |mode| {
+            edit_message(EditChange::Normalize(mode))
+        }),
+    ]
+    .spacing(20)
+    .align_y(Alignment::Center);
+    let target = match edits.normalize {
+        Normalize::Off => None,
+        Normalize::Peak => Some((edits.peak_target, -12.0, 0.0, "dBFS")),
+        Normalize::Loudness => Some((edits.loudness_target, -30.0, -6.0, "LUFS")),
+    };
+    if let Some((value, min, max, unit)) = target {
+        normalize = normalize
+            .push(text(format!("{:.1} {}", value, unit)).width(Length::Fixed(90.0)))
+            .push(
+                slider(std::ops::RangeInclusive::new(min, max), value, |val| {
+                    edit_message(EditChange::NormalizeTarget(val))
+                })
+                .step(0.1)
+                .on_release(Message::Editor(EditorEvent::Apply)),
+            );
+    }
+
+    let mut export = row![
+        button(text("Reset trim"))
+            .style(button::secondary)
+            .on_press(edit_message(EditChange::ResetTrim)),
+        button(text("Export"))
+            .style(button::success)
+            .on_press(Message::Editor(EditorEvent::Export)),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+    if let Some(path) = &editor.exported {
+        export = export
+            .push(
+                button(text(icon_to_string(Bootstrap::BoxArrowUpRight)).font(ICON_FONT))
+                    .style(button::text)
+                    .on_press(Message::DragSample(path.clone())),
+            )
+            .push(
+                button(text(icon_to_string(Bootstrap::Copy)).font(ICON_FONT))
+                    .style(button::text)
+                    .on_press(Message::CopySample(path.clone())),
+            );
+    }
+
+    column![text("Trim & fades").size(25), fades, normalize, export]
+        .spacing(15)
+        .padding(20)
+        .into()
+}
+
+fn transform_message(change: TransformChange) -> Message {
+    Message::Editor(EditorEvent::Transform(change))
+}
+
+fn transform_view(editor: &Editor) -> Element<Message> {
+    let transform = &editor.transform;
+    let preview = Message::Editor(EditorEvent::Preview);
+    let controls = row![
+        checkbox("Reverse", transform.reverse)
+            .on_toggle(|val| transform_message(TransformChange::Reverse(val))),
+        text("Gain"),
+        text(format!("{:+.1} dB", transform.gain)).width(Length::Fixed(70.0)),
+        slider(
+            std::ops::RangeInclusive::new(-24.0, 24.0),
+            transform.gain,
+            |val| transform_message(TransformChange::Gain(val))
+        )
+        .step(0.5)
+        .on_release(preview.clone()),
+        text("Pitch"),
+        text(format!("{:+} st", transform.semitones)).width(Length::Fixed(50.0)),
+        slider(
+            std::ops::RangeInclusive::new(-24, 24),
+            transform.semitones,
+            |val| transform_message(TransformChange::Semitones(val))
+        )
+        .on_release(preview.clone()),
+        text(format!("{:+} c", transform.cents)).width(Length::Fixed(50.0)),
+        slider(
+            std::ops::RangeInclusive::new(-100, 100),
+            transform.cents,
+            |val| transform_message(TransformChange::Cents(val))
+        )
+        .on_release(preview),
+    ]
+    .spacing(15)
+    .align_y(Alignment::Center);
+
+    let pending = !transform.is_empty();
+    let actions = row![
+        button(text("Commit"))
+            .style(button::success)
+            .on_press_maybe(pending.then_some(Message::Editor(EditorEvent::CommitTransform))),
+        button(text("Discard"))
+            .style(button::secondary)
+            .on_press_maybe(pending.then_some(Message::Editor(EditorEvent::DiscardTransform))),
+    ]
+    .spacing(10);
+
+    column![text("Transform").size(25), controls, actions]
+        .spacing(15)
+        .padding(20)
+        .into()
+}
+
+fn eq_message(change: EqChange) -> Message {
+    Message::Editor(EditorEvent::Eq(change))
+}
+
+fn freq_slider<'a>(freq: f32, f: impl Fn(f32) -> EqChange + 'a) -> Element<'a, Message> {
+    row![
+        text(dsp::format_freq(freq)).width(Length::Fixed(80.0)),
+        slider(
+            std::ops::RangeInclusive::new(0.0, 1.0),
+            dsp::position_from_freq(freq),
+            move |pos| eq_message(f(dsp::freq_from_position(pos)))
+        )
+        .step(0.001)
+        .on_release(Message::Editor(EditorEvent::Apply))
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+fn q_slider<'a>(label: &'a str, q: f32, f: impl Fn(f32) -> EqChange + 'a) -> Element<'a, Message> {
+    row![
+        text(label),
+        text(format!("{:.1}", q)).width(Length::Fixed(40.0)),
+        slider(std::ops::RangeInclusive::new(0.5, 10.0), q, move |q| {
+            eq_message(f(q))
+        })
+        .step(0.1)
+        .on_release(Message::Editor(EditorEvent::Apply))
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+fn pass_filter<'a>(
+    kind: FilterKind,
+    cutoff: Option<f32>,
+    q: f32,
+    toggle: fn(bool) -> EqChange,
+    freq: fn(f32) -> EqChange,
+    resonance: fn(f32) -> EqChange,
+) -> Element<'a, Message> {
+    let mut filter = row![checkbox(kind.to_string(), cutoff.is_some())
+        .on_toggle(move |val| eq_message(toggle(val)))
+        .width(Length::Fixed(120.0))]
+    .spacing(20)
+    .align_y(Alignment::Center);
+    if let Some(cutoff) = cutoff {
+        filter = filter
+            .push(freq_slider(cutoff, freq))
+            .push(q_slider("Resonance", q, resonance));
+    }
+    filter.into()
+}
+
+fn equalizer(eq: &Equalizer) -> Element<Message> {
+    let mut bands = column![
+        pass_filter(
+            FilterKind::HighPass,
+            eq.highpass,
+            eq.highpass_q,
+            EqChange::Highpass,
+            EqChange::HighpassFreq,
+            EqChange::HighpassQ
+        ),
+        pass_filter(
+            FilterKind::LowPass,
+            eq.lowpass,
+            eq.lowpass_q,
+            EqChange::Lowpass,
+            EqChange::LowpassFreq,
+            EqChange::LowpassQ
+        ),
+    ]
+    .spacing(10);
+    for (i, band) in eq.bands.iter().enumerate() {
+        let mut entry = row![
+            text(band.kind.to_string()).width(Length::Fixed(120.0)),
+            text(format!("{:+.1} dB", band.gain)).width(Length::Fixed(70.0)),
+            slider(
+                std::ops::RangeInclusive::new(-18.0, 18.0),
+                band.gain,
+                move |gain| eq_message(EqChange::BandGain(i, gain))
+            )
+            .step(0.5)
+            .on_release(Message::Editor(EditorEvent::Apply)),
+            freq_slider(band.freq, move |freq| EqChange::BandFreq(i, freq)),
+        ]
+        .spacing(20)
+        .align_y(Alignment::Center);
+        if band.kind == FilterKind::Peaking {
+            entry = entry.push(q_slider("Q", band.q, move |q| EqChange::BandQ(i, q)));
+        }
+        bands = bands.push(entry);
+    }
+    column![text("Equalizer").size(25), bands]
+        .spacing(15)
+        .padding(20)
+        .into()
+}
+
 //fn transport_bar() -> Element<Message> {}
 
 pub fn view(app: &AudioCloud) -> Element<Message> {
@@ -97,25 +899,117 @@ pub fn view(app: &AudioCloud) -> Element<Message> {
             .align_y(Alignment::Center),
     );
 
+    let editor = &app.editor;
+    let play_icon = match editor.is_playing() {
+        true => Bootstrap::Pause,
+        false => Bootstrap::Play,
+    };
+    let loop_text = match editor.looping() {
+        true => text(icon_to_string(Bootstrap::Repeat)).style(text::success),
+        false => text(icon_to_string(Bootstrap::Repeat)),
+    };
+    let time = format!(
+        "{} / {}",
+        audio::format_duration(
+            editor.duration_of(editor.transport.position.load(Ordering::Relaxed))
+        ),
+        audio::format_duration(editor.duration_of(editor.len()))
+    );
+
+    // A plays the processed audio, B the original
+    let bypassed = editor.bypass.load(Ordering::Relaxed);
     let transport_bar = row![
-        button(text(icon_to_string(Bootstrap::Play)).font(ICON_FONT))
-            .on_press(Message::Editor(EditorEvent::PlaybackStart))
-    ];
+        button(text(icon_to_string(play_icon)).font(ICON_FONT))
+            .on_press(Message::Editor(EditorEvent::TogglePlayback)),
+        button(text(icon_to_string(Bootstrap::Stop)).font(ICON_FONT))
+            .on_press(Message::Editor(EditorEvent::StopPlayback)),
+        button(loop_text.font(ICON_FONT))
+            .style(button::text)
+            .on_press(Message::Editor(EditorEvent::ToggleLoop)),
+        button(text("Loop start"))
+            .style(button::secondary)
+            .on_press(Message::Editor(EditorEvent::SetLoopStart)),
+        button(text("Loop end"))
+            .style(button::secondary)
+            .on_press(Message::Editor(EditorEvent::SetLoopEnd)),
+        button(text("Silence region"))
+            .style(button::secondary)
+            .on_press_maybe(
+                editor
+                    .looping()
+                    .then_some(Message::Editor(EditorEvent::SilenceRegion))
+            ),
+        text(time),
+        horizontal_space(),
+        button(text(match bypassed {
+            true => "B: Original",
+            false => "A: Processed",
+        }))
+        .style(match bypassed {
+            true => button::secondary,
+            false => button::primary,
+        })
+        .on_press(Message::Editor(EditorEvent::ToggleBypass)),
+    ]
+    .spacing(10)
+    .padding(20)
+    .align_y(Alignment::Center);
 
     let wav = container(
-        waveform(app.editor.wav).color(
-            app.selected_theme
-                .clone()
-                .unwrap()
-                .extended_palette()
-                .primary
-                .base
-                .color,
-        ),
+        waveform(app.editor.wav)
+            .color(
+                app.selected_theme
+                    .clone()
+                    .unwrap()
+                    .extended_palette()
+                    .primary
+                    .base
+                    .color,
+            )
+            .playhead(editor.playhead())
+            .region(editor.looping().then(|| editor.region()))
+            .trim(editor.edits.trim)
+            .on_seek(|pos| Message::Editor(EditorEvent::Seek(pos)))
+            .on_trim(|start, end| edit_message(EditChange::Trim(start, end)))
+            .on_trim_release(Message::Editor(EditorEvent::Apply)),
     )
     .style(themes::container_front);
 
-    let eq = column![text("Equalizer").size(25),];
+    let history = &editor.history;
+    let history_bar = row![
+        button(text(icon_to_string(Bootstrap::ArrowCounterclockwise)).font(ICON_FONT))
+            .style(button::text)
+            .on_press_maybe(
+                (!history.ops.is_empty() || !editor.transform.is_empty())
+                    .then_some(Message::Editor(EditorEvent::Undo))
+            ),
+        button(text(icon_to_string(Bootstrap::ArrowClockwise)).font(ICON_FONT))
+            .style(button::text)
+            .on_press_maybe(
+                (!history.undone.is_empty()).then_some(Message::Editor(EditorEvent::Redo))
+            ),
+        text(format!("{} edits", history.ops.len())).style(themes::text_fg),
+        button(text("Revert to original"))
+            .style(button::danger)
+            .on_press(Message::Editor(EditorEvent::Revert)),
+    ]
+    .spacing(10)
+    .padding(20)
+    .align_y(Alignment::Center);
+
+    let eq = equalizer(&app.editor.eq);
+    let edits = edits_view(&app.editor);
+    let transform = transform_view(&app.editor);
 
-    column![status_bar, text("Editor"), wav, eq, transport_bar].into()
+    column![
+        status_bar,
+        text(editor.display_name()).size(25),
+        history_bar,
+        wav,
+        transport_bar,
+        eq,
+        edits,
+        transform
+    ]
+    .into()
 }
@@ -8,6 +8,10 @@ impl Error {
     pub fn new(t: ErrorType) -> Self {
         Error { e: t }
     }
+    // The server couldn't be reached at all, as opposed to answering with garbage
+    pub fn is_offline(&self) -> bool {
+        matches!(self.e, ErrorType::Connection | ErrorType::Timeout)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -17,10 +21,24 @@ pub enum ErrorType {
     JSON,
     FileOpen,
     FileSave,
+    Timeout,
+    Server,
+    Url,
+    Cancelled,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Errored")
+        match self.e {
+            ErrorType::Parse => write!(f, "Couldnt parse response"),
+            ErrorType::Connection => write!(f, "Failed to connect"),
+            ErrorType::JSON => write!(f, "Invalid JSON"),
+            ErrorType::FileOpen => write!(f, "Couldnt open file"),
+            ErrorType::FileSave => write!(f, "Couldnt save file"),
+            ErrorType::Timeout => write!(f, "Request timed out"),
+            ErrorType::Server => write!(f, "Server error"),
+            ErrorType::Url => write!(f, "Invalid server URL"),
+            ErrorType::Cancelled => write!(f, "Cancelled"),
+        }
     }
 }
@@ -18,14 +18,18 @@ pub fn remove_brackets(input: &str) -> String {
     result
 }
 
-pub fn hash_sample(path: &str) -> String {
-    xxh3_64(path.replace(".wav", "").as_bytes()).to_string()
+// Identifies a server independent of surrounding whitespace and trailing slashes
+pub fn server_id(server_url: &str) -> String {
+    server_url.trim().trim_end_matches('/').to_string()
 }
 
-// deletes all files in "cached" and returns an empty vector to be used as list of downloaded files
-pub async fn clear_cached() -> Vec<String> {
-    let path = "cached/";
-    fs::remove_dir_all(path).unwrap();
-    fs::create_dir(path).unwrap();
-    vec![]
+// Two servers may both have "Drums/kick.wav", so the server is part of the hash
+pub fn hash_sample(server_url: &str, path: &str) -> String {
+    xxh3_64((server_id(server_url) + "\n" + path).as_bytes()).to_string()
+}
+
+// Written next to the target first and renamed over it, so a crash never leaves half a file
+pub fn write_atomic(path: &str, contents: &str) -> bool {
+    let temp = path.to_string() + ".tmp";
+    fs::write(&temp, contents).is_ok() && fs::rename(&temp, path).is_ok()
 }
@@ -0,0 +1,246 @@
+use iced::widget::{button, column, container, row, scrollable, text, text_input, Row};
+use iced::{Alignment, Element, Length, Task};
+use serde_derive::*;
+
+use crate::bootstrap::*;
+use crate::local::SearchSource;
+use crate::search::SearchView;
+use crate::{themes, AudioCloud, Message, PackInfo, ICON_FONT};
+
+const MAX_HISTORY: usize = 20;
+
+// A query with everything needed to restore the filters it ran with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+    pub show_oneshots: bool,
+    pub show_loops: bool,
+    pub show_only_favourites: bool,
+    pub show_all_favourites: bool,
+    pub min_tempo: Option<i32>,
+    pub max_tempo: Option<i32>,
+    pub half_double_time: bool,
+    pub pack_ids: Vec<String>,
+    // Searches from before sources existed all ran on the server
+    #[serde(default)]
+    pub source: SearchSource,
+}
+impl SavedSearch {
+    pub fn capture(app: &AudioCloud, name: &str) -> Self {
+        let options = &app.search_options;
+        SavedSearch {
+            name: name.to_string(),
+            query: app.input.clone(),
+            show_oneshots: options.show_oneshots,
+            show_loops: options.show_loops,
+            show_only_favourites: options.show_only_favourites,
+            show_all_favourites: options.show_all_favourites,
+            min_tempo: options.min_tempo,
+            max_tempo: options.max_tempo,
+            half_double_time: options.half_double_time,
+            pack_ids: app.search_view_state.pack_ids.clone(),
+            source: options.source,
+        }
+    }
+
+    fn same_filters(&self, other: &SavedSearch) -> bool {
+        SavedSearch {
+            name: other.name.clone(),
+            ..self.clone()
+        } == *other
+    }
+
+    // Short description of the filters, like "Loops, 90-110 bpm, 2 packs"
+    pub fn summary(&self, meta: &[PackInfo]) -> String {
+        let mut parts = vec![];
+        if self.source != SearchSource::Server {
+            parts.push(self.source.to_string());
+        }
+        match (self.show_oneshots, self.show_loops) {
+            (true, false) => parts.push(String::from("OneShots")),
+            (false, true) => parts.push(String::from("Loops")),
+            _ => (),
+        }
+        if self.show_only_favourites {
+            parts.push(String::from("Favourites"));
+        }
+        match (self.min_tempo, self.max_tempo) {
+            (None, None) => (),
+            (Some(min), None) => parts.push(format!("> {} bpm", min)),
+            (None, Some(max)) => parts.push(format!("< {} bpm", max)),
+            (Some(min), Some(max)) => parts.push(format!("{}-{} bpm", min, max)),
+        }
+        match self.pack_ids.as_slice() {
+            [] => (),
+            [id] => parts.push(match meta.iter().find(|p| &p.id == id) {
+                Some(pack) => pack.name.clone(),
+                None => id.clone(),
+            }),
+            ids => parts.push(format!("{} packs", ids.len())),
+        }
+        parts.join(", ")
+    }
+}
+
+// Searches run while typing replace the one they only extended or shortened
+pub fn record(app: &mut AudioCloud) {
+    if app.input.is_empty() {
+        return;
+    }
+    let entry = SavedSearch::capture(app, &app.input);
+    let history = &mut app.settings.search_history;
+    let typed_on = history.first().is_some_and(|top| {
+        let query = SavedSearch {
+            query: entry.query.clone(),
+            ..top.clone()
+        };
+        query.same_filters(&entry)
+            && (entry.query.starts_with(&top.query) || top.query.starts_with(&entry.query))
+    });
+    if typed_on {
+        history.remove(0);
+    }
+    history.retain(|s| !s.same_filters(&entry));
+    history.insert(0, entry);
+    history.truncate(MAX_HISTORY);
+}
+
+pub fn save(app: &mut AudioCloud, name: String) {
+    let name = match name.trim() {
+        "" => app.input.clone(),
+        val => val.to_string(),
+    };
+    if name.is_empty() {
+        return;
+    }
+    let entry = SavedSearch::capture(app, &name);
+    app.settings.saved_searches.retain(|s| s.name != name);
+    app.settings.saved_searches.push(entry);
+}
+
+pub fn restore(app: &mut AudioCloud, search: SavedSearch) -> Task<Message> {
+    let options = &mut app.search_options;
+    options.show_oneshots = search.show_oneshots;
+    options.show_loops = search.show_loops;
+    options.show_only_favourites = search.show_only_favourites;
+    options.show_all_favourites = search.show_all_favourites;
+    options.min_tempo = search.min_tempo;
+    options.max_tempo = search.max_tempo;
+    options.half_double_time = search.half_double_time;
+    if options.source != search.source {
+        options.source = search.source;
+        app.local_results = None;
+    }
+
+    let state = &mut app.search_view_state;
+    state.pack_ids = search.pack_ids.clone();
+    state.min_tempo_input = search.min_tempo.map(|t| t.to_string()).unwrap_or_default();
+    state.max_tempo_input = search.max_tempo.map(|t| t.to_string()).unwrap_or_default();
+    state.show_history_overlay = false;
+    state.show_pack_overlay = false;
+    state.show_tempo_overlay = false;
+
+    app.input = search.query.clone();
+    record(app);
+    app.create_request_command(app.input.clone())
+}
+
+fn search_entry<'a>(
+    search: &'a SavedSearch,
+    meta: &'a [PackInfo],
+    action: Element<'a, Message>,
+) -> Element<'a, Message> {
+    let mut label = column![text(search.name.clone())];
+    let summary = search.summary(meta);
+    if !summary.is_empty() || search.name != search.query {
+        let detail = match search.name == search.query {
+            true => summary,
+            false if summary.is_empty() => search.query.clone(),
+            false => format!("{}, {}", search.query, summary),
+        };
+        label = label.push(text(detail).size(12).style(themes::text_fg));
+    }
+    row![
+        button(label.spacing(2))
+            .style(button::text)
+            .width(Length::Fill)
+            .on_press(Message::SearchView(SearchView::RunSearch(search.clone()))),
+        action
+    ]
+    .align_y(Alignment::Center)
+    .into()
+}
+
+pub fn history_dropdown(app: &AudioCloud) -> Element<Message> {
+    let save_row = row![
+        text_input("Name", &app.search_view_state.save_name_input)
+            .on_input(|val| Message::SearchView(SearchView::SaveNameInput(val)))
+            .on_submit(Message::SearchView(SearchView::SaveSearch)),
+        button(text("Save current"))
+            .style(button::secondary)
+            .on_press(Message::SearchView(SearchView::SaveSearch)),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let mut saved = column![text("Saved").size(18)].spacing(5);
+    for (i, search) in app.settings.saved_searches.iter().enumerate() {
+        let unpin = button(text(icon_to_string(Bootstrap::PinFill)).font(ICON_FONT))
+            .style(button::text)
+            .on_press(Message::SearchView(SearchView::UnpinSearch(i)));
+        saved = saved.push(search_entry(search, &app.pack_meta, unpin.into()));
+    }
+
+    let mut recent = column![row![
+        text("Recent").size(18).width(Length::Fill),
+        button(text("Clear").size(14))
+            .style(button::text)
+            .on_press(Message::SearchView(SearchView::ClearHistory)),
+    ]
+    .align_y(Alignment::Center)]
+    .spacing(5);
+    if app.settings.search_history.is_empty() {
+        recent = recent.push(text("No recent searches").style(themes::text_fg));
+    }
+    for (i, search) in app.settings.search_history.iter().enumerate() {
+        let pin = button(text(icon_to_string(Bootstrap::Pin)).font(ICON_FONT))
+            .style(button::text)
+            .on_press(Message::SearchView(SearchView::PinHistory(i)));
+        recent = recent.push(search_entry(search, &app.pack_meta, pin.into()));
+    }
+
+    container(
+        column![save_row, scrollable(column![saved, recent].spacing(15))]
+            .spacing(15)
+            .height(Length::Shrink),
+    )
+    .style(container::rounded_box)
+    .width(Length::Fixed(400.0))
+    .max_height(500.0)
+    .padding(10)
+    .into()
+}
+
+// Pinned searches stay one click away under the search box
+pub fn saved_chips(app: &AudioCloud) -> Element<Message> {
+    let mut chips = Row::new().spacing(5);
+    for search in &app.settings.saved_searches {
+        chips = chips.push(
+            button(
+                row![
+                    text(icon_to_string(Bootstrap::PinFill))
+                        .font(ICON_FONT)
+                        .size(14),
+                    text(search.name.clone()).size(14)
+                ]
+                .spacing(5)
+                .align_y(Alignment::Center),
+            )
+            .style(themes::round_button)
+            .padding([2, 8])
+            .on_press(Message::SearchView(SearchView::RunSearch(search.clone()))),
+        );
+    }
+    chips.into()
+}
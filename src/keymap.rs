@@ -0,0 +1,334 @@
+use core::fmt;
+use iced::keyboard::{key, Key, Modifiers};
+use iced::widget::{button, column, row, text, Row};
+use iced::{Alignment, Element, Length, Padding, Task};
+use serde_derive::*;
+use std::time::Duration;
+
+use crate::bootstrap::*;
+use crate::editor::EditorEvent;
+use crate::request;
+use crate::search;
+use crate::settings::SettingsChanged;
+use crate::status::StatusBarLevel;
+use crate::{themes, AudioCloud, Message, ViewControl, ICON_FONT};
+
+const SEEK_STEP: Duration = Duration::from_secs(5);
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    PlayPause,
+    NextResult,
+    PreviousResult,
+    Favourite,
+    Download,
+    SeekForward,
+    SeekBackward,
+    VolumeUp,
+    VolumeDown,
+    OpenEditor,
+    OpenSettings,
+    FocusSearch,
+    Undo,
+    Redo,
+}
+impl Action {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Action::PlayPause,
+            Action::NextResult,
+            Action::PreviousResult,
+            Action::Favourite,
+            Action::Download,
+            Action::SeekForward,
+            Action::SeekBackward,
+            Action::VolumeUp,
+            Action::VolumeDown,
+            Action::OpenEditor,
+            Action::OpenSettings,
+            Action::FocusSearch,
+            Action::Undo,
+            Action::Redo,
+        ]
+    }
+}
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::PlayPause => write!(f, "Play / pause"),
+            Action::NextResult => write!(f, "Next result"),
+            Action::PreviousResult => write!(f, "Previous result"),
+            Action::Favourite => write!(f, "Favourite"),
+            Action::Download => write!(f, "Download"),
+            Action::SeekForward => write!(f, "Seek forward"),
+            Action::SeekBackward => write!(f, "Seek backward"),
+            Action::VolumeUp => write!(f, "Volume up"),
+            Action::VolumeDown => write!(f, "Volume down"),
+            Action::OpenEditor => write!(f, "Open in editor"),
+            Action::OpenSettings => write!(f, "Open settings"),
+            Action::FocusSearch => write!(f, "Focus search"),
+            Action::Undo => write!(f, "Undo edit"),
+            Action::Redo => write!(f, "Redo edit"),
+        }
+    }
+}
+
+// A key with the modifiers held down, characters are stored lowercase
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyChord {
+    pub key: String,
+    pub command: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+impl KeyChord {
+    fn new(key: &str) -> Self {
+        KeyChord {
+            key: key.to_string(),
+            command: false,
+            shift: false,
+            alt: false,
+        }
+    }
+    fn command(mut self) -> Self {
+        self.command = true;
+        self
+    }
+    fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    // None for keys that can't be bound on their own, like modifiers
+    pub fn from_key(key: &Key, modifiers: Modifiers) -> Option<Self> {
+        let (name, shift) = match key.as_ref() {
+            Key::Named(
+                key::Named::Shift | key::Named::Control | key::Named::Alt | key::Named::Super,
+            ) => return None,
+            Key::Named(named) => (format!("{:?}", named), modifiers.shift()),
+            // Shift is already part of symbols like "/", only letters keep it
+            Key::Character(c) => {
+                let letter = c.chars().all(|c| c.is_alphabetic());
+                (c.to_lowercase(), letter && modifiers.shift())
+            }
+            Key::Unidentified => return None,
+        };
+        Some(KeyChord {
+            key: name,
+            command: modifiers.command(),
+            shift,
+            alt: modifiers.alt(),
+        })
+    }
+}
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        match self.key.chars().count() {
+            1 => write!(f, "{}", self.key.to_uppercase()),
+            _ => write!(f, "{}", self.key),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Binding {
+    pub action: Action,
+    pub chord: KeyChord,
+}
+
+// Actions can have several chords, but a chord only ever triggers one action
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Keymap {
+    pub bindings: Vec<Binding>,
+}
+impl Default for Keymap {
+    fn default() -> Self {
+        let bind = |action: Action, chord: KeyChord| Binding { action, chord };
+        Keymap {
+            bindings: vec![
+                bind(Action::PlayPause, KeyChord::new("Space")),
+                bind(Action::NextResult, KeyChord::new("ArrowDown")),
+                bind(Action::PreviousResult, KeyChord::new("ArrowUp")),
+                bind(Action::Favourite, KeyChord::new("f")),
+                bind(Action::Download, KeyChord::new("d")),
+                bind(Action::SeekForward, KeyChord::new("ArrowRight")),
+                bind(Action::SeekBackward, KeyChord::new("ArrowLeft")),
+                bind(Action::VolumeUp, KeyChord::new("ArrowUp").command()),
+                bind(Action::VolumeDown, KeyChord::new("ArrowDown").command()),
+                bind(Action::OpenEditor, KeyChord::new("e")),
+                bind(Action::OpenSettings, KeyChord::new(",").command()),
+                bind(Action::FocusSearch, KeyChord::new("f").command()),
+                bind(Action::FocusSearch, KeyChord::new("/")),
+                bind(Action::Undo, KeyChord::new("z").command()),
+                bind(Action::Redo, KeyChord::new("z").command().shift()),
+            ],
+        }
+    }
+}
+impl Keymap {
+    pub fn action(&self, chord: &KeyChord) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|b| &b.chord == chord)
+            .map(|b| b.action)
+    }
+    pub fn chords(&self, action: Action) -> Vec<&KeyChord> {
+        self.bindings
+            .iter()
+            .filter(|b| b.action == action)
+            .map(|b| &b.chord)
+            .collect()
+    }
+    // Refuses chords that already trigger another action and returns that action
+    pub fn bind(&mut self, action: Action, chord: KeyChord) -> Result<(), Action> {
+        match self.action(&chord) {
+            Some(bound) if bound == action => Ok(()),
+            Some(bound) => Err(bound),
+            None => {
+                self.bindings.push(Binding { action, chord });
+                Ok(())
+            }
+        }
+    }
+    pub fn unbind(&mut self, chord: &KeyChord) {
+        self.bindings.retain(|b| &b.chord != chord);
+    }
+}
+
+pub fn key_pressed(app: &mut AudioCloud, key: Key, modifiers: Modifiers) -> Task<Message> {
+    let chord = match KeyChord::from_key(&key, modifiers) {
+        None => return Task::none(),
+        Some(val) => val,
+    };
+    if let Some(action) = app.settings_state.recording {
+        return record(app, action, chord, key);
+    }
+    let action = match app.settings.keymap.action(&chord) {
+        None => return Task::none(),
+        Some(val) => val,
+    };
+    // Undo and redo act on the editor, everything else on the search view
+    let message = match (action, &app.view) {
+        (Action::OpenSettings, _) => Message::SettingsButtonToggled,
+        (Action::Undo, ViewControl::Editor) => Message::Editor(EditorEvent::Undo),
+        (Action::Redo, ViewControl::Editor) => Message::Editor(EditorEvent::Redo),
+        (Action::Undo | Action::Redo, _) => return Task::none(),
+        (_, ViewControl::Main) => return perform(app, action),
+        _ => return Task::none(),
+    };
+    Task::perform(request::nothing(), move |()| message)
+}
+
+fn perform(app: &mut AudioCloud, action: Action) -> Task<Message> {
+    let message = match action {
+        Action::PlayPause => Message::TogglePlayer,
+        Action::SeekForward | Action::SeekBackward => {
+            let dur = match app.player.duration {
+                Some(dur) if !dur.is_zero() => dur,
+                _ => return Task::none(),
+            };
+            let pos = match action {
+                Action::SeekForward => (app.player.position + SEEK_STEP).min(dur),
+                _ => app.player.position.saturating_sub(SEEK_STEP),
+            };
+            Message::SeekPlayer(pos.as_secs_f32() / dur.as_secs_f32())
+        }
+        Action::VolumeUp => Message::VolumeChanged((app.player.volume + VOLUME_STEP).min(2.0)),
+        Action::VolumeDown => Message::VolumeChanged((app.player.volume - VOLUME_STEP).max(0.0)),
+        _ => return search::result_action(app, action),
+    };
+    Task::perform(request::nothing(), move |()| message)
+}
+
+// Binds the next key pressed in the settings view, escape cancels
+fn record(app: &mut AudioCloud, action: Action, chord: KeyChord, key: Key) -> Task<Message> {
+    app.settings_state.recording = None;
+    if let Key::Named(key::Named::Escape) = key {
+        return Task::none();
+    }
+    match app.settings.keymap.bind(action, chord.clone()) {
+        Ok(()) => {
+            app.settings_state.keymap_conflict = None;
+            app.status.set(
+                StatusBarLevel::Succes,
+                &format!("Bound {} to {}", chord, action),
+            );
+        }
+        Err(bound) => {
+            let conflict = format!("{} is already bound to {}", chord, bound);
+            app.status.set(StatusBarLevel::Danger, &conflict);
+            app.settings_state.keymap_conflict = Some((action, conflict));
+        }
+    }
+    Task::none()
+}
+
+pub fn keymap_editor(app: &AudioCloud) -> Element<Message> {
+    let mut list = column![].spacing(10).padding(Padding {
+        top: 0.0,
+        bottom: 0.0,
+        left: 40.0,
+        right: 20.0,
+    });
+    for action in Action::all() {
+        let mut chords = Row::new().spacing(5);
+        for chord in app.settings.keymap.chords(action) {
+            chords = chords.push(
+                button(
+                    row![
+                        text(chord.to_string()).size(14),
+                        text(icon_to_string(Bootstrap::X)).font(ICON_FONT).size(14)
+                    ]
+                    .spacing(5),
+                )
+                .style(themes::round_button)
+                .on_press(Message::Settings(SettingsChanged::RemoveBinding(
+                    chord.clone(),
+                ))),
+            );
+        }
+        let add_label = match app.settings_state.recording == Some(action) {
+            true => "Press a key...",
+            false => "Add",
+        };
+        let mut entry = row![
+            text(action.to_string()).width(Length::Fixed(160.0)),
+            chords,
+            button(text(add_label))
+                .style(button::text)
+                .on_press(Message::Settings(SettingsChanged::RecordBinding(action))),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15);
+        if let Some((conflicting, conflict)) = &app.settings_state.keymap_conflict {
+            if *conflicting == action {
+                entry = entry.push(text(conflict.clone()).style(text::danger));
+            }
+        }
+        list = list.push(entry);
+    }
+    column![
+        row![
+            text("Key bindings:"),
+            button(text("Restore defaults"))
+                .style(button::danger)
+                .on_press(Message::Settings(SettingsChanged::RestoreKeymap)),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15),
+        list
+    ]
+    .spacing(15)
+    .padding(20)
+    .into()
+}
@@ -0,0 +1,269 @@
+use audiocloud_lib::Sample;
+use rodio::{Decoder, Source};
+use serde_derive::*;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use iced::Task;
+
+use crate::editor;
+use crate::error::{Error, ErrorType};
+use crate::helpers;
+use crate::local;
+use crate::{AudioCloud, Message, SampleType};
+
+// Library samples carry their file in the path
+pub const LIBRARY_PREFIX: &str = "library://";
+const INDEX_FILE: &str = "library.json";
+const INDEX_BROKEN: &str = "library.json.broken";
+const EXTENSIONS: [&str; 6] = ["wav", "aif", "aiff", "flac", "mp3", "ogg"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LibraryEntry {
+    pub file: String,
+    pub name: String,
+    pub sampletype: SampleType,
+    pub modified: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LibraryIndex {
+    pub entries: Vec<LibraryEntry>,
+    // Unchanged folders aren't read again
+    #[serde(default)]
+    pub folders: HashMap<String, u64>,
+    #[serde(skip)]
+    pub loaded: bool,
+}
+impl LibraryIndex {
+    pub fn samples(&self) -> Vec<Sample> {
+        self.entries
+            .iter()
+            .map(|e| Sample {
+                name: e.name.clone(),
+                path: LIBRARY_PREFIX.to_string() + &e.file,
+                sampletype: e.sampletype.clone(),
+            })
+            .collect()
+    }
+    fn same_as(&self, other: &LibraryIndex) -> bool {
+        self.folders == other.folders
+            && self.entries.len() == other.entries.len()
+            && self
+                .entries
+                .iter()
+                .zip(&other.entries)
+                .all(|(a, b)| a.file == b.file && a.modified == b.modified)
+    }
+}
+
+pub fn is_library_path(path: &str) -> bool {
+    path.starts_with(LIBRARY_PREFIX)
+}
+
+pub fn file_of(path: &str) -> Option<PathBuf> {
+    path.strip_prefix(LIBRARY_PREFIX).map(PathBuf::from)
+}
+
+// 1 to 16 bars of 4/4 at a plausible tempo are taken as a loop
+fn tempo_from_duration(duration: Duration) -> Option<u32> {
+    let secs = duration.as_secs_f32();
+    if secs < 2.0 {
+        return None;
+    }
+    for bars in [1, 2, 4, 8, 16] {
+        let tempo = bars as f32 * 4.0 * 60.0 / secs;
+        if (70.0..=180.0).contains(&tempo) && (tempo - tempo.round()).abs() < 0.05 {
+            return Some(tempo.round() as u32);
+        }
+    }
+    None
+}
+
+fn infer_sampletype(name: &str, file: &Path) -> SampleType {
+    // "loop" in the name without a tempo still needs the duration for one
+    match local::infer_sampletype(name) {
+        SampleType::OneShot | SampleType::Loop(0) => (),
+        val => return val,
+    }
+    let duration = File::open(file)
+        .ok()
+        .and_then(|f| Decoder::new(BufReader::new(f)).ok())
+        .and_then(|d| d.total_duration());
+    match duration.and_then(tempo_from_duration) {
+        Some(tempo) => SampleType::Loop(tempo as _),
+        None => SampleType::OneShot,
+    }
+}
+
+fn is_audio(file: &Path) -> bool {
+    match file.extension().and_then(|e| e.to_str()) {
+        None => false,
+        Some(ext) => EXTENSIONS.contains(&ext.to_lowercase().as_str()),
+    }
+}
+
+// There is no file watcher, folders are polled and only read again if they changed
+pub async fn scan(folders: Vec<String>, old: LibraryIndex) -> LibraryIndex {
+    tokio::task::spawn_blocking(move || scan_folders(&folders, old))
+        .await
+        .unwrap_or_default()
+}
+
+fn modified_at(path: &Path) -> Option<Duration> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+}
+
+fn parent_of(file: &str) -> PathBuf {
+    Path::new(file)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+fn scan_folders(folders: &[String], old: LibraryIndex) -> LibraryIndex {
+    let known: HashMap<(&str, u64), &LibraryEntry> = old
+        .entries
+        .iter()
+        .map(|e| ((e.file.as_str(), e.modified), e))
+        .collect();
+    let mut files_in: HashMap<PathBuf, Vec<&LibraryEntry>> = HashMap::new();
+    for entry in &old.entries {
+        files_in
+            .entry(parent_of(&entry.file))
+            .or_default()
+            .push(entry);
+    }
+    let mut folders_in: HashMap<PathBuf, Vec<&String>> = HashMap::new();
+    for folder in old.folders.keys() {
+        folders_in
+            .entry(parent_of(folder))
+            .or_default()
+            .push(folder);
+    }
+    let mut entries = vec![];
+    let mut scanned = HashMap::new();
+    let mut dirs: Vec<PathBuf> = folders.iter().map(PathBuf::from).collect();
+    while let Some(dir) = dirs.pop() {
+        let modified = match modified_at(&dir) {
+            None => continue,
+            Some(val) => val.as_nanos() as u64,
+        };
+        let key = dir.to_string_lossy().to_string();
+        if old.folders.get(&key) == Some(&modified) {
+            if let Some(files) = files_in.get(&dir) {
+                entries.extend(files.iter().map(|e| (*e).clone()));
+            }
+            if let Some(subdirs) = folders_in.get(&dir) {
+                dirs.extend(subdirs.iter().map(PathBuf::from));
+            }
+            scanned.insert(key, modified);
+            continue;
+        }
+        let items = match fs::read_dir(&dir) {
+            Err(_) => continue,
+            Ok(val) => val,
+        };
+        scanned.insert(key, modified);
+        for item in items.flatten() {
+            let path = item.path();
+            // Symlinked folders are skipped, they could point back up the tree
+            match item.file_type() {
+                Ok(t) if t.is_dir() => {
+                    dirs.push(path);
+                    continue;
+                }
+                Err(_) => continue,
+                Ok(_) if !is_audio(&path) => continue,
+                Ok(_) => (),
+            }
+            let modified = modified_at(&path).map(|d| d.as_secs()).unwrap_or(0);
+            let file = path.to_string_lossy().to_string();
+            if let Some(entry) = known.get(&(file.as_str(), modified)) {
+                entries.push((*entry).clone());
+                continue;
+            }
+            let name = match path.file_name() {
+                None => continue,
+                Some(val) => val.to_string_lossy().to_string(),
+            };
+            entries.push(LibraryEntry {
+                sampletype: infer_sampletype(&name, &path),
+                file,
+                name,
+                modified,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.file.cmp(&b.file));
+    LibraryIndex {
+        entries,
+        folders: scanned,
+        loaded: true,
+    }
+}
+
+pub async fn load_index() -> LibraryIndex {
+    let mut index: LibraryIndex = match fs::read_to_string(INDEX_FILE) {
+        Err(_) => LibraryIndex::default(),
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|_| {
+            let _ = fs::rename(INDEX_FILE, INDEX_BROKEN);
+            LibraryIndex::default()
+        }),
+    };
+    index.loaded = true;
+    index
+}
+
+pub async fn save_index(index: LibraryIndex) {
+    if let Ok(content) = serde_json::to_string_pretty(&index) {
+        helpers::write_atomic(INDEX_FILE, &content);
+    }
+}
+
+pub async fn copy_to_editor(sample: Sample) -> Result<(Sample, String), Error> {
+    match file_of(&sample.path) {
+        None => Err(Error::new(ErrorType::FileOpen)),
+        Some(file) => editor::copy_to_editor(sample, file).await,
+    }
+}
+
+// Files edited in place don't change their folder, a manual rescan reads every folder
+pub fn rescan_all(app: &mut AudioCloud) -> Task<Message> {
+    if app.library_scanning {
+        return Task::none();
+    }
+    app.library.folders.clear();
+    rescan(app)
+}
+
+pub fn rescan(app: &mut AudioCloud) -> Task<Message> {
+    if app.library_scanning || !app.library.loaded || app.settings.library_folders.is_empty() {
+        return Task::none();
+    }
+    app.library_scanning = true;
+    Task::perform(
+        scan(app.settings.library_folders.clone(), app.library.clone()),
+        Message::LibraryScanned,
+    )
+}
+
+pub fn loaded(app: &mut AudioCloud, index: LibraryIndex) -> Task<Message> {
+    app.library = index;
+    rescan(app)
+}
+
+pub fn scanned(app: &mut AudioCloud, index: LibraryIndex) -> Task<Message> {
+    app.library_scanning = false;
+    if !index.loaded || index.same_as(&app.library) {
+        return Task::none();
+    }
+    app.library = index;
+    Task::perform(save_index(app.library.clone()), Message::Nothing)
+}
@@ -0,0 +1,233 @@
+use audiocloud_lib::Sample;
+use core::fmt;
+use serde_derive::*;
+
+use crate::{helpers, AudioCloud, SampleType};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum SearchSource {
+    #[default]
+    Server,
+    Local,
+    Library,
+}
+impl SearchSource {
+    pub fn all() -> Vec<Self> {
+        vec![
+            SearchSource::Server,
+            SearchSource::Local,
+            SearchSource::Library,
+        ]
+    }
+}
+impl std::fmt::Display for SearchSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchSource::Server => write!(f, "Server"),
+            SearchSource::Local => write!(f, "Local"),
+            SearchSource::Library => write!(f, "Local library"),
+        }
+    }
+}
+
+// Tempo from names like "drums_120bpm.wav", "bpm 95 groove" or "loop-128-bpm"
+pub fn infer_tempo(name: &str) -> Option<u32> {
+    let chars: Vec<char> = name.to_lowercase().chars().collect();
+    let is_sep = |c: char| c == '_' || c == '-' || c == ' ' || c == '.';
+    for start in 0..chars.len().saturating_sub(2) {
+        if chars[start..start + 3] != ['b', 'p', 'm'] {
+            continue;
+        }
+        // Digits right before or after, separators allowed in between
+        let mut end = start;
+        while end > 0 && is_sep(chars[end - 1]) {
+            end -= 1;
+        }
+        let mut begin = end;
+        while begin > 0 && chars[begin - 1].is_ascii_digit() {
+            begin -= 1;
+        }
+        let before: String = chars[begin..end].iter().collect();
+        let mut begin = start + 3;
+        while begin < chars.len() && is_sep(chars[begin]) {
+            begin += 1;
+        }
+        let mut end = begin;
+        while end < chars.len() && chars[end].is_ascii_digit() {
+            end += 1;
+        }
+        let after: String = chars[begin..end].iter().collect();
+        for digits in [before, after] {
+            match digits.parse::<u32>() {
+                Ok(tempo) if (40..=300).contains(&tempo) => return Some(tempo),
+                _ => (),
+            }
+        }
+    }
+    None
+}
+
+// Loops are recognised by a tempo or the word "loop" in the name
+pub fn infer_sampletype(name: &str) -> SampleType {
+    match infer_tempo(name) {
+        Some(tempo) => SampleType::Loop(tempo as _),
+        None if tokens(name).iter().any(|t| t == "loop" || t == "loops") => SampleType::Loop(0),
+        None => SampleType::OneShot,
+    }
+}
+
+fn tokens(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+        .collect()
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|n| chars.any(|h| h == n))
+}
+
+fn token_score(token: &str, word: &str) -> u32 {
+    if word == token {
+        4
+    } else if word.starts_with(token) {
+        3
+    } else if word.contains(token) {
+        2
+    } else if is_subsequence(token, word) {
+        1
+    } else {
+        0
+    }
+}
+
+// Every query token has to match some word of the name or path, better matches score higher
+pub fn score(query: &str, sample: &Sample) -> Option<u32> {
+    let words = tokens(&(sample.name.clone() + " " + &sample.path));
+    let mut total = 0;
+    for token in tokens(query) {
+        let best = words
+            .iter()
+            .map(|word| token_score(&token, word))
+            .max()
+            .unwrap_or(0);
+        if best == 0 {
+            return None;
+        }
+        total += best;
+    }
+    Some(total)
+}
+
+pub fn local_samples(app: &AudioCloud) -> Vec<Sample> {
+    let mut samples = app.settings.favourite_samples.clone();
+    let server = helpers::server_id(&app.settings.server_url);
+    for entry in app.cache.entries.iter().filter(|e| e.server == server) {
+        if samples.iter().any(|s| s.path == entry.path) {
+            continue;
+        }
+        samples.push(Sample {
+            name: entry.original_name.clone(),
+            path: entry.path.clone(),
+            sampletype: entry
+                .sampletype
+                .clone()
+                .unwrap_or_else(|| infer_sampletype(&entry.original_name)),
+        });
+    }
+    samples
+}
+
+pub fn search(app: &AudioCloud, query: &str) -> Vec<Sample> {
+    search_in(
+        app,
+        local_samples(app),
+        query,
+        &app.search_view_state.pack_ids,
+    )
+}
+
+// Library folders aren't server packs, so the pack filter doesn't apply
+pub fn search_library(app: &AudioCloud, query: &str) -> Vec<Sample> {
+    search_in(app, app.library.samples(), query, &[])
+}
+
+fn search_in(app: &AudioCloud, samples: Vec<Sample>, query: &str, packs: &[String]) -> Vec<Sample> {
+    let options = &app.search_options;
+    let mut scored: Vec<(u32, Sample)> = samples
+        .into_iter()
+        .filter(|s| match s.sampletype {
+            SampleType::OneShot => options.show_oneshots,
+            SampleType::Loop(_) => options.show_loops,
+        })
+        .filter(|s| {
+            let path = s.path.trim_start_matches('/');
+            packs.is_empty()
+                || packs
+                    .iter()
+                    .any(|p| path.starts_with(&(p.to_string() + "/")))
+        })
+        .filter_map(|s| score(query, &s).map(|score| (score, s)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    scored.into_iter().map(|(_, s)| s).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str, path: &str) -> Sample {
+        Sample {
+            name: name.to_string(),
+            path: path.to_string(),
+            sampletype: SampleType::OneShot,
+        }
+    }
+
+    #[test]
+    fn tempo_before_and_after_bpm() {
+        assert_eq!(infer_tempo("drums_120bpm.wav"), Some(120));
+        assert_eq!(infer_tempo("bpm 95 groove"), Some(95));
+        assert_eq!(infer_tempo("Loop-128-BPM.wav"), Some(128));
+    }
+
+    #[test]
+    fn tempo_out_of_range_or_missing() {
+        assert_eq!(infer_tempo("kick_909.wav"), None);
+        assert_eq!(infer_tempo("bass_1000bpm.wav"), None);
+        assert_eq!(infer_tempo("bpm.wav"), None);
+    }
+
+    #[test]
+    fn sampletype_from_name() {
+        assert!(matches!(
+            infer_sampletype("pad_90bpm.wav"),
+            SampleType::Loop(t) if t as u32 == 90
+        ));
+        assert!(matches!(
+            infer_sampletype("drum loop.wav"),
+            SampleType::Loop(t) if t as u32 == 0
+        ));
+        assert!(matches!(infer_sampletype("snare.wav"), SampleType::OneShot));
+    }
+
+    #[test]
+    fn better_matches_score_higher() {
+        let kick = sample("kick.wav", "Drums/kick.wav");
+        let exact = score("kick", &kick).unwrap();
+        let prefix = score("kic", &kick).unwrap();
+        let inner = score("ick", &kick).unwrap();
+        let scattered = score("kk", &kick).unwrap();
+        assert!(exact > prefix && prefix > inner && inner > scattered);
+    }
+
+    #[test]
+    fn every_token_has_to_match() {
+        let kick = sample("kick.wav", "Drums/kick.wav");
+        assert!(score("drums kick", &kick).is_some());
+        assert!(score("kick snare", &kick).is_none());
+    }
+}
@@ -8,8 +8,8 @@ use audiocloud_lib::*;
 use clipboard_rs::{Clipboard, ClipboardContext};
 
 use editor::{Editor, EditorEvent};
-use helpers::hash_sample;
 use iced::event::{self, Event};
+use iced::keyboard;
 use iced::widget::{
     button, checkbox, column, combo_box, container, horizontal_space, row, scrollable, text,
     text_input, tooltip, vertical_space,
@@ -18,24 +18,32 @@ use iced::window;
 use iced::{alignment, Alignment, Element, Font, Length, Padding, Subscription, Task, Theme};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
-use request::get_editor_audio;
 use rodio::{source::Source, Decoder};
 use settings::{settings_changed, SettingsChanged};
 use status::*;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::*;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub mod audio;
 pub mod bootstrap;
+pub mod cache;
+pub mod downloads;
+pub mod dsp;
 pub mod editor;
 pub mod error;
 pub mod helpers;
+pub mod history;
+pub mod keymap;
+pub mod library;
+pub mod local;
+pub mod operations;
 pub mod overlay_anchor;
 pub mod request;
 pub mod search;
 pub mod settings;
+pub mod sorting;
 pub mod status;
 pub mod themes;
 pub mod waveform;
@@ -55,10 +63,14 @@ async fn main() -> iced::Result {
         .run_with(|| {
             (
                 AudioCloud::new().0,
-                Task::perform(
-                    settings::load_from_file("settings.json"),
-                    Message::SettingsLoaded,
-                ),
+                Task::batch([
+                    Task::perform(
+                        settings::load_from_file("settings.json"),
+                        Message::SettingsLoaded,
+                    ),
+                    Task::perform(library::load_index(), Message::LibraryLoaded),
+                    Task::perform(audio::list_output_devices(), Message::AudioDevicesListed),
+                ]),
             )
         })
 }
@@ -79,6 +91,15 @@ pub struct AudioCloud {
 
     pack_meta: Vec<PackInfo>,
 
+    api: request::ApiClient,
+    search_generation: u64,
+    last_search: Option<SearchParams>,
+    local_results: Option<Vec<Sample>>,
+    downloads: downloads::DownloadManager,
+    cache: cache::CacheIndex,
+    library: library::LibraryIndex,
+    library_scanning: bool,
+
     settings: settings::Settings,
     status: StatusBar,
 
@@ -103,7 +124,7 @@ pub enum Message {
 
     GoView(ViewControl),
     EditorSessionDL(Sample),
-    EditorSession((Sample, String)),
+    EditorSession(Result<(Sample, String), error::Error>),
     Editor(EditorEvent),
 
     SearchView(search::SearchView),
@@ -115,16 +136,27 @@ pub enum Message {
     InputChanged(String),
     CreateTask,
     SettingsButtonToggled,
-    SearchResultRecived(Result<SearchResult, error::Error>),
+    SearchDebounced(u64),
+    SearchResultRecived(u64, Result<request::SearchPage, error::Error>),
+    LoadMore,
+    MoreResultsRecived(u64, Result<request::SearchPage, error::Error>),
     ServerStatusUpdate(bool),
     ServerUrlSubmited(String),
 
-    PlaySample(String, String),
-    TempAudioLoaded(String),
-    DownloadSample(String),
-    SampleAudioDownloaded(String),
-    SamplePlayDone(Instant),
+    PlaySample(Sample),
+    TempAudioLoaded(Result<String, error::Error>),
+    PlayerAudioDecoded(Result<std::sync::Arc<audio::Decoded>, error::Error>),
+    DownloadSample(Sample),
+    Download(downloads::DownloadEvent),
+    SampleAudioDownloaded(Result<cache::CacheEntry, error::Error>),
+    PlayerTick(Instant),
+    AudioDeviceCheck(Instant),
+    AudioDevicesListed(Vec<String>),
+    PlayerWaveformLoaded([f32; ARRAYLEN as usize]),
+    SeekPlayer(f32),
+    ToggleLoop,
     TogglePlayer,
+    StopPlayer,
     VolumeChanged(f32),
 
     ThemeSelected(Theme),
@@ -143,7 +175,11 @@ pub enum Message {
     PacksMetaRecived(Result<Vec<PackInfo>, error::Error>),
     ResetSettings,
     ResetCache,
-    CacheReset(Vec<String>),
+    CacheReset(cache::CacheIndex),
+    CacheLoaded(cache::CacheIndex),
+    LibraryLoaded(library::LibraryIndex),
+    LibraryScanned(library::LibraryIndex),
+    LibraryCheck(Instant),
 
     ToggleFavourite(Sample),
     ShuffleResults,
@@ -153,28 +189,64 @@ pub enum Message {
 
 const ICON_FONT: Font = Font::with_name("bootstrap-icons");
 
-fn perform_search(params: SearchParams, path: String) -> Task<Message> {
-    Task::perform(
-        request::get_result(params, path),
-        Message::SearchResultRecived,
-    )
+fn perform_search(
+    api: request::ApiClient,
+    params: SearchParams,
+    path: String,
+    generation: u64,
+) -> Task<Message> {
+    Task::perform(api.get_first_page(params, path), move |res| {
+        Message::SearchResultRecived(generation, res)
+    })
 }
-fn send_file_preview_dl(server_url: String, path: String) -> Task<Message> {
+fn perform_search_packs(
+    api: request::ApiClient,
+    params: SearchParams,
+    packs: Vec<String>,
+    path: String,
+    generation: u64,
+) -> Task<Message> {
     Task::perform(
-        request::get_temp_audio(server_url, path),
-        Message::TempAudioLoaded,
+        api.get_result_packs(params, packs, 0, vec![], path),
+        move |res| Message::SearchResultRecived(generation, res),
     )
 }
-fn send_file_dl(server_url: String, path: String) -> Task<Message> {
+fn send_file_preview_dl(
+    api: request::ApiClient,
+    server_url: String,
+    path: String,
+) -> Task<Message> {
     Task::perform(
-        request::dl_sample(server_url, path),
-        Message::SampleAudioDownloaded,
+        api.get_temp_audio(server_url, path),
+        Message::TempAudioLoaded,
     )
 }
 
 impl AudioCloud {
-    fn create_request_command(&self, input: String) -> Task<Message> {
+    // File on disk for a sample, library samples are played and dragged in place
+    fn sample_file(&self, path: &str) -> Option<PathBuf> {
+        match library::file_of(path) {
+            Some(file) if file.exists() => Some(file),
+            Some(_) => None,
+            None => self.cache.file_path(&self.settings.server_url, path),
+        }
+    }
+    // Responses of older searches are dropped on arrival
+    fn create_request_command(&mut self, input: String) -> Task<Message> {
+        self.search_generation += 1;
         if input.is_empty() || input.eq("-") {
+            self.last_search = None;
+            return Task::none();
+        }
+        if self.search_options.source != local::SearchSource::Server {
+            history::record(self);
+            self.last_search = None;
+            self.search_view_state.selected = None;
+            self.search_view_state.has_more = false;
+            self.local_results = match self.search_options.source {
+                local::SearchSource::Library => Some(local::search_library(self, &input)),
+                _ => Some(local::search(self, &input)),
+            };
             return Task::none();
         }
 
@@ -189,27 +261,65 @@ impl AudioCloud {
                 }
             };
 
+        let (min_tempo, max_tempo) = self.search_options.tempo_range();
+
         let params = SearchParams {
             query: input.clone(),
             sample_type: sample_type_filter,
-            max_tempo: None,
-            min_tempo: None,
-            pack_id: self.search_view_state.pack_id.clone(),
+            max_tempo,
+            min_tempo,
+            pack_id: self.search_view_state.pack_ids.first().cloned(),
             max_results: Some(self.settings.max_results),
         };
-        return perform_search(params, self.settings.server_url.clone());
+        self.last_search = Some(params.clone());
+        self.search_view_state.has_more = false;
+        self.search_view_state.loading_more = false;
+        if self.search_view_state.pack_ids.len() > 1 {
+            return perform_search_packs(
+                self.api.clone(),
+                params,
+                self.search_view_state.pack_ids.clone(),
+                self.settings.server_url.clone(),
+                self.search_generation,
+            );
+        }
+        return perform_search(
+            self.api.clone(),
+            params,
+            self.settings.server_url.clone(),
+            self.search_generation,
+        );
+    }
+    fn debounce_search(&mut self) -> Task<Message> {
+        if self.settings.search_debounce_ms == 0 {
+            return self.create_request_command(self.input.clone());
+        }
+        self.search_generation += 1;
+        let generation = self.search_generation;
+        let delay = Duration::from_millis(self.settings.search_debounce_ms as u64);
+        Task::perform(tokio::time::sleep(delay), move |()| {
+            Message::SearchDebounced(generation)
+        })
     }
     fn new() -> (Self, Task<Message>) {
+        let mut status = StatusBar::new();
+        let audio_devices = match audio::init_audio(None) {
+            Ok(handlers) => Some(handlers),
+            Err(e) => {
+                status.set(
+                    StatusBarLevel::Danger,
+                    &format!("Audio output unavailable: {}", e),
+                );
+                None
+            }
+        };
         (
             Self {
                 input: String::from(""),
                 view: ViewControl::Main,
                 results: None,
                 server_status: None,
-                audio_devices: match audio::init_audio() {
-                    Ok(handlers) => Some(handlers),
-                    Err(_) => None,
-                },
+                audio_devices,
                 theme_state: combo_box::State::new(Theme::ALL.to_vec()),
                 selected_theme: None,
 
@@ -218,15 +328,29 @@ impl AudioCloud {
 
                 pack_meta: vec![],
 
+                api: request::ApiClient::new(request::ApiConfig::default()),
+                search_generation: 0,
+                last_search: None,
+                local_results: None,
+                downloads: downloads::DownloadManager::new(),
+                cache: cache::CacheIndex::default(),
+                library: library::LibraryIndex::default(),
+                library_scanning: false,
+
                 settings_state: settings::SettingsState::new(),
                 settings: settings::Settings::default(),
-                status: StatusBar::new(),
+                status,
 
                 player: widgets::Player {
                     is_playing: false,
                     name: "None".to_string(),
                     volume: 1.0,
-                    last_update_playing: Instant::now(),
+                    audio: None,
+                    position: Duration::ZERO,
+                    duration: None,
+                    last_tick: Instant::now(),
+                    wav: [0.0; ARRAYLEN as usize],
+                    looping: false,
                 },
                 editor: Editor::empty(),
             },
@@ -239,17 +363,64 @@ impl AudioCloud {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        event::listen().map(Message::EventOccurred)
+        let events = event::listen().map(Message::EventOccurred);
+        let device_check = iced::time::every(Duration::from_secs(3)).map(Message::AudioDeviceCheck);
+        let mut subscriptions = vec![events, device_check];
+        // Ticks drive the playhead and notice when the sink runs out of audio
+        if self.player.is_playing {
+            subscriptions
+                .push(iced::time::every(Duration::from_millis(50)).map(Message::PlayerTick));
+        }
+        if self.editor.is_playing() {
+            subscriptions.push(
+                iced::time::every(Duration::from_millis(50))
+                    .map(|t| Message::Editor(EditorEvent::Tick(t))),
+            );
+        }
+        // Unchanged library folders only cost a look at their modification time
+        if !self.settings.library_folders.is_empty() {
+            subscriptions
+                .push(iced::time::every(Duration::from_secs(30)).map(Message::LibraryCheck));
+        }
+        Subscription::batch(subscriptions)
     }
 
+    // The result order is only worked out again after messages that can change it
     fn update(&mut self, message: Message) -> Task<Message> {
+        let reorder = matches!(
+            message,
+            Message::SearchResultRecived(..)
+                | Message::MoreResultsRecived(..)
+                | Message::SearchDebounced(_)
+                | Message::CreateTask
+                | Message::InputChanged(_)
+                | Message::SearchView(_)
+                | Message::ShowOneshotsCheckbox(_)
+                | Message::ShowLoopsCheckbox(_)
+                | Message::ShowOnlyFavouritesToggled(_)
+                | Message::ShowAllFavourites
+                | Message::ToggleFavourite(_)
+                | Message::ShuffleResults
+                | Message::PlaySample(_)
+                | Message::PacksMetaRecived(_)
+                | Message::SettingsLoaded(_)
+                | Message::ResetSettings
+        );
+        let task = self.handle_message(message);
+        if reorder {
+            search::refresh_order(self);
+        }
+        task
+    }
+
+    fn handle_message(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::SearchView(msg) => return search::search_update(msg, self),
             Message::Nothing(_) => (),
             Message::Editor(event) => return editor::editor_event(self, event),
             Message::Settings(val) => return settings_changed(self, val),
-            Message::EventOccurred(event) => {
-                if let Event::Window(window::Event::CloseRequested) = event {
+            Message::EventOccurred(event) => match event {
+                Event::Window(window::Event::CloseRequested) => {
                     let mut set = self.settings.clone();
                     match &self.selected_theme {
                         Some(theme) => set.theme = theme.to_string(),
@@ -260,46 +431,137 @@ impl AudioCloud {
                         settings::save_to_file(set, "settings.json"),
                         Message::Exit,
                     );
-                } else {
-                    return Task::none();
                 }
-            }
+                Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                    return keymap::key_pressed(self, key, modifiers);
+                }
+                _ => (),
+            },
             Message::Exit(_) => {
-                println!("Saved!");
                 return window::get_latest().and_then(window::close);
             }
             Message::RecivedHandle => {}
             Message::InputChanged(val) => {
                 self.input = val;
+                return self.debounce_search();
+            }
+            Message::SearchDebounced(generation) => {
+                if generation == self.search_generation {
+                    return self.create_request_command(self.input.clone());
+                }
+            }
+            Message::CreateTask => {
                 return self.create_request_command(self.input.clone());
             }
-            Message::CreateTask => {}
             Message::SettingsButtonToggled => match self.view {
                 ViewControl::Settings => self.view = ViewControl::Main,
                 _ => self.view = ViewControl::Settings,
             },
-            Message::SearchResultRecived(v) => {
-                let val = match v {
-                    Err(_) => {
-                        self.status.set(StatusBarLevel::Danger, "Failed to connect");
+            Message::SearchResultRecived(generation, v) => {
+                if generation != self.search_generation {
+                    return Task::none();
+                }
+                if !matches!(&v, Err(e) if !e.is_offline()) {
+                    history::record(self);
+                }
+                let page = match v {
+                    // Fall back to favourites and downloads while the server is unreachable
+                    Err(e) if e.is_offline() => {
+                        self.status.set(
+                            StatusBarLevel::Danger,
+                            &format!("{}, showing local results", e),
+                        );
+                        self.search_view_state.selected = None;
+                        self.search_view_state.has_more = false;
+                        self.local_results = Some(local::search(self, &self.input));
+                        return Task::none();
+                    }
+                    Err(e) => {
+                        self.status.set(StatusBarLevel::Danger, &e.to_string());
                         return Task::none();
                     }
 
-                    Ok(val) => val,
+                    Ok(page) => page,
                 };
+                self.local_results = None;
+                self.search_view_state.selected = None;
+                self.search_view_state.pages_loaded = 1;
+                self.search_view_state.has_more = page.has_more;
+                let val = page.result;
                 if val.samples.len() > 0 {
                     self.results = Some(val)
                 } else if !self.search_options.show_all_favourites {
                     self.results = None
                 }
             }
+            Message::LoadMore => {
+                let params = match &self.last_search {
+                    Some(params)
+                        if self.local_results.is_none()
+                            && self.search_view_state.has_more
+                            && !self.search_view_state.loading_more
+                            && !self.search_options.show_all_favourites =>
+                    {
+                        params.clone()
+                    }
+                    _ => return Task::none(),
+                };
+                let seen: Vec<String> = match &self.results {
+                    Some(res) => res.samples.iter().map(|s| s.path.clone()).collect(),
+                    None => return Task::none(),
+                };
+                self.search_view_state.loading_more = true;
+                let offset =
+                    self.search_view_state.pages_loaded * self.settings.max_results as usize;
+                let generation = self.search_generation;
+                let api = self.api.clone();
+                let server_url = self.settings.server_url.clone();
+                let packs = self.search_view_state.pack_ids.clone();
+                let on_result = move |res| Message::MoreResultsRecived(generation, res);
+                if packs.len() > 1 {
+                    return Task::perform(
+                        api.get_result_packs(params, packs, offset, seen, server_url),
+                        on_result,
+                    );
+                }
+                return Task::perform(
+                    api.get_result_page(params, offset, seen, server_url),
+                    on_result,
+                );
+            }
+            Message::MoreResultsRecived(generation, res) => {
+                if generation != self.search_generation {
+                    return Task::none();
+                }
+                self.search_view_state.loading_more = false;
+                let page = match res {
+                    Err(e) => {
+                        self.status.set(StatusBarLevel::Danger, &e.to_string());
+                        return Task::none();
+                    }
+                    Ok(page) => page,
+                };
+                self.search_view_state.pages_loaded += 1;
+                self.search_view_state.has_more = page.has_more;
+                if let Some(results) = &mut self.results {
+                    let new: Vec<Sample> = page
+                        .result
+                        .samples
+                        .into_iter()
+                        .filter(|s| !results.samples.iter().any(|r| r.path == s.path))
+                        .collect();
+                    results.samples.extend(new);
+                }
+            }
             Message::ServerUrlSubmited(url) => {
                 self.settings.server_url = url;
                 if !self.settings.server_url.ends_with("/") {
                     self.settings.server_url.push('/');
                 }
                 return Task::perform(
-                    request::check_connection(self.settings.server_url.clone()),
+                    self.api
+                        .clone()
+                        .check_connection(self.settings.server_url.clone()),
                     Message::ServerStatusUpdate,
                 );
             }
@@ -307,62 +569,154 @@ impl AudioCloud {
                 self.server_status = Some(status);
             }
 
-            Message::PlaySample(path, name) => {
-                self.player.name = name;
-                println!("{}", path);
-                return send_file_preview_dl(self.settings.server_url.clone(), path);
+            Message::PlaySample(sample) => {
+                sorting::played(self, &sample.path);
+                let path = sample.path;
+                self.player.name = sample.name;
+                self.player.looping =
+                    self.settings.loop_preview && matches!(sample.sampletype, SampleType::Loop(_));
+                if let Some(file) = self.sample_file(&path) {
+                    let file = file.to_string_lossy().to_string();
+                    return Task::batch([
+                        cache::touch(self, &path),
+                        Task::perform(request::nothing(), move |()| {
+                            Message::TempAudioLoaded(Ok(file))
+                        }),
+                    ]);
+                }
+                return send_file_preview_dl(
+                    self.api.clone(),
+                    self.settings.server_url.clone(),
+                    path,
+                );
+            }
+            Message::TempAudioLoaded(res) => {
+                let path = match res {
+                    Err(e) => {
+                        self.player.name = String::from("None");
+                        self.status.set(StatusBarLevel::Danger, &e.to_string());
+                        return Task::none();
+                    }
+                    Ok(val) => val,
+                };
+                return Task::perform(audio::decode_file(path), Message::PlayerAudioDecoded);
             }
-            Message::TempAudioLoaded(path) => {
-                let file = BufReader::new(File::open(&path).expect("Couldnt open file"));
-                let source_file = Decoder::new(file);
-                let source = match source_file {
+            Message::PlayerAudioDecoded(res) => {
+                let decoded = match res {
                     Err(_) => {
                         self.status
                             .set(StatusBarLevel::Danger, "Couldnt open downloaded file");
                         return Task::none();
                     }
-                    Ok(decoder) => decoder,
+                    Ok(val) => val,
                 };
                 //let source_r = source.buffered().reverb(Duration::from_millis(40), 0.7);
                 match &self.audio_devices {
                     Some(devs) => {
-                        if !devs.sink.empty() {
-                            devs.sink.clear();
-                        }
-
-                        let dur = &source.total_duration();
+                        audio::load(&devs.sink, &decoded, self.player.looping, Duration::ZERO);
                         devs.sink.set_volume(self.player.volume);
-                        devs.sink.append(source);
                         devs.sink.play();
                         self.player.is_playing = true;
 
-                        let now = Instant::now();
-                        self.player.last_update_playing = now;
-                        return audio::wait_playback_end(dur.clone(), now);
+                        self.player.last_tick = Instant::now();
+                        self.player.position = Duration::ZERO;
+                        self.player.duration = Some(decoded.duration());
+                        self.player.audio = Some(decoded.clone());
+                        return Task::perform(
+                            waveform::get_waveform_decoded(decoded),
+                            Message::PlayerWaveformLoaded,
+                        );
                     }
-                    None => println!("Error loading devices from option"),
+                    None => self.status.set(StatusBarLevel::Danger, audio::NO_OUTPUT),
                 }
             }
-            Message::SamplePlayDone(mod_stamp) => {
-                if mod_stamp == self.player.last_update_playing {
+            Message::PlayerTick(now) => {
+                let finished = match &self.audio_devices {
+                    Some(devs) => devs.sink.empty(),
+                    None => true,
+                };
+                // The sample played out, keep it loaded so play starts it over
+                if self.player.is_playing && finished {
                     self.player.is_playing = false;
-                    self.player.name = String::from("None");
+                    self.player.position = Duration::ZERO;
+                } else if self.player.is_playing {
+                    self.player.position += now.saturating_duration_since(self.player.last_tick);
+                    match self.player.duration {
+                        Some(dur) if self.player.looping && !dur.is_zero() => {
+                            let wrapped = self.player.position.as_secs_f64() % dur.as_secs_f64();
+                            self.player.position = Duration::from_secs_f64(wrapped);
+                        }
+                        Some(dur) => self.player.position = self.player.position.min(dur),
+                        None => (),
+                    }
+                }
+                self.player.last_tick = now;
+            }
+            Message::AudioDeviceCheck(_) => {
+                return Task::perform(audio::list_output_devices(), Message::AudioDevicesListed)
+            }
+            Message::AudioDevicesListed(names) => audio::check_device(self, names),
+            Message::PlayerWaveformLoaded(wav) => {
+                self.player.wav = wav;
+            }
+            Message::SeekPlayer(fraction) => {
+                let (dur, decoded) = match (self.player.duration, &self.player.audio) {
+                    (Some(dur), Some(decoded)) => (dur, decoded),
+                    _ => return Task::none(),
+                };
+                let pos = dur.mul_f32(fraction.clamp(0.0, 1.0));
+                match &self.audio_devices {
+                    Some(devs) => {
+                        audio::seek(&devs.sink, decoded, pos, self.player.looping);
+                        self.player.position = pos;
+                        self.player.last_tick = Instant::now();
+                    }
+                    None => self.status.set(StatusBarLevel::Danger, audio::NO_OUTPUT),
+                }
+            }
+            Message::ToggleLoop => {
+                self.player.looping = !self.player.looping;
+                let decoded = match &self.player.audio {
+                    Some(decoded) => decoded,
+                    None => return Task::none(),
+                };
+                let pos = self.player.position;
+                match &self.audio_devices {
+                    Some(devs) if devs.sink.empty() => (),
+                    Some(devs) => audio::seek(&devs.sink, decoded, pos, self.player.looping),
+                    None => self.status.set(StatusBarLevel::Danger, audio::NO_OUTPUT),
                 }
             }
             Message::TogglePlayer => match &self.audio_devices {
                 Some(devs) => {
-                    if !devs.sink.empty() {
-                        if devs.sink.is_paused() {
-                            devs.sink.play();
-                        } else {
-                            devs.sink.pause();
-                        }
-                        self.player.is_playing = !self.player.is_playing;
-                        self.player.last_update_playing = Instant::now();
+                    if devs.sink.empty() {
+                        // Finished or stopped, start the last sample over
+                        let decoded = match &self.player.audio {
+                            None => return Task::none(),
+                            Some(val) => val,
+                        };
+                        audio::load(&devs.sink, decoded, self.player.looping, Duration::ZERO);
+                        self.player.position = Duration::ZERO;
+                        devs.sink.play();
+                        self.player.is_playing = true;
+                    } else if devs.sink.is_paused() {
+                        devs.sink.play();
+                        self.player.is_playing = true;
+                    } else {
+                        devs.sink.pause();
+                        self.player.is_playing = false;
                     }
+                    self.player.last_tick = Instant::now();
                 }
-                None => println!("Error loading devices from option"),
+                None => self.status.set(StatusBarLevel::Danger, audio::NO_OUTPUT),
             },
+            Message::StopPlayer => {
+                if let Some(devs) = &self.audio_devices {
+                    devs.sink.clear();
+                }
+                self.player.is_playing = false;
+                self.player.position = Duration::ZERO;
+            }
             Message::VolumeChanged(val) => {
                 self.player.volume = val;
                 match &self.audio_devices {
@@ -398,14 +752,30 @@ impl AudioCloud {
             Message::SettingsLoaded(val) => {
                 self.settings = val;
                 self.selected_theme = themes::string_to_theme(&self.settings.clone().theme);
+                self.api = request::ApiClient::new(self.settings.api_config());
                 self.status.set(StatusBarLevel::Neutral, "Loaded settings");
-                return Task::perform(
-                    request::get_packs_meta(self.settings.server_url.clone()),
-                    Message::PacksMetaRecived,
-                );
+                let device = self.audio_devices.as_ref().map(|devs| devs.device.clone());
+                if self.settings.output_device.is_some() && self.settings.output_device != device {
+                    audio::reconnect(self);
+                }
+                return Task::batch([
+                    cache::load(self),
+                    library::rescan(self),
+                    Task::perform(
+                        self.api
+                            .clone()
+                            .get_packs_meta(self.settings.server_url.clone()),
+                        Message::PacksMetaRecived,
+                    ),
+                ]);
             }
+            Message::LibraryLoaded(index) => return library::loaded(self, index),
+            Message::LibraryScanned(index) => return library::scanned(self, index),
+            Message::LibraryCheck(_) => return library::rescan(self),
             Message::PacksMetaRecived(m) => match m {
-                Err(_) => self.status.set(StatusBarLevel::Danger, "Failed to get IDs"),
+                Err(e) => self
+                    .status
+                    .set(StatusBarLevel::Danger, &format!("Failed to get IDs: {}", e)),
                 Ok(metas) => {
                     self.pack_meta = metas;
                     self.status.set(StatusBarLevel::Neutral, "Recived PackIDs");
@@ -436,28 +806,36 @@ impl AudioCloud {
             Message::MaxRequestsChanged(val) => {
                 self.settings.max_results = val;
             }
-            Message::DownloadSample(path) => {
-                return send_file_dl(self.settings.server_url.clone(), path);
-            }
-            Message::SampleAudioDownloaded(path) => {
-                self.settings.add_dl_entry(&path);
-                self.status.set(StatusBarLevel::Succes, "Downloaded sample");
+            Message::DownloadSample(sample) => {
+                return downloads::enqueue(self, sample);
             }
+            Message::Download(event) => return downloads::download_event(self, event),
+            Message::SampleAudioDownloaded(res) => match res {
+                Err(e) => self
+                    .status
+                    .set(StatusBarLevel::Danger, &format!("Download failed: {}", e)),
+                Ok(entry) => {
+                    self.status.set(StatusBarLevel::Succes, "Downloaded sample");
+                    return cache::store(self, entry);
+                }
+            },
             Message::DragSample(path) => {
-                return window::get_latest().and_then(|id| {
-                    let val = path.clone();
+                let file = match self.sample_file(&path) {
+                    None => {
+                        self.status
+                            .set(StatusBarLevel::Danger, "Sample missing from cache");
+                        return Task::none();
+                    }
+                    Some(val) => val,
+                };
+                let drag_task = window::get_latest().and_then(move |id| {
+                    let file = file.clone();
                     window::run_with_handle(id, move |handle| {
                         window::get_latest();
-                        let rel_str =
-                            String::from("cached/".to_string() + &hash_sample(&path) + ".wav");
                         let preview_icon =
                             drag::Image::Raw(include_bytes!("../audio.png").to_vec());
 
-                        let relative_path = Path::new(&rel_str);
-                        let item =
-                            drag::DragItem::Files(vec![
-                                std::fs::canonicalize(relative_path).expect("Couldnt make path")
-                            ]);
+                        let item = drag::DragItem::Files(vec![file]);
                         let opts = drag::Options::default();
                         let _ = drag::start_drag(
                             &handle,
@@ -474,23 +852,27 @@ impl AudioCloud {
                         Message::DragPerformed
                     })
                 });
+                return Task::batch([cache::touch(self, &path), drag_task]);
             }
             Message::CopySample(path) => {
                 #[cfg(not(target_arch = "wasm32"))]
                 {
-                    let rel_str =
-                        String::from("cached/".to_string() + &hash_sample(&path) + ".wav");
-                    let relative_path = Path::new(&rel_str);
-                    let mut absolute_path = std::env::current_dir().unwrap();
-                    absolute_path.push(relative_path);
-                    let abs_str: String = absolute_path.to_str().unwrap().to_string();
+                    let absolute_path = match self.sample_file(&path) {
+                        None => {
+                            self.status
+                                .set(StatusBarLevel::Danger, "Sample missing from cache");
+                            return Task::none();
+                        }
+                        Some(val) => val,
+                    };
+                    let abs_str: String = absolute_path.to_string_lossy().to_string();
                     let filepath = vec![abs_str];
 
                     let ctx = ClipboardContext::new().expect("Couldnt init clipboard");
                     ctx.set_files(filepath).expect("couldnt set to clipboard");
                     self.status.set(StatusBarLevel::Succes, "Copied sample");
 
-                    return Task::none();
+                    return cache::touch(self, &path);
                 }
                 #[cfg(target_arch = "wasm32")]
                 {
@@ -512,39 +894,56 @@ impl AudioCloud {
                 );
             }
             Message::ResetCache => {
-                return Task::perform(helpers::clear_cached(), Message::CacheReset);
+                return Task::perform(cache::clear(), Message::CacheReset);
             }
             Message::CacheReset(val) => {
-                self.settings.dl_samples_hash = val;
+                self.cache = val;
+                return cache::save(self);
+            }
+            Message::CacheLoaded(val) => {
+                return cache::loaded(self, val);
             }
             Message::GoView(v) => self.view = v,
             Message::EditorSessionDL(sample) => {
+                if library::is_library_path(&sample.path) {
+                    return Task::perform(library::copy_to_editor(sample), Message::EditorSession);
+                }
                 return Task::perform(
-                    get_editor_audio(sample, self.settings.server_url.clone()),
+                    self.api
+                        .clone()
+                        .get_editor_audio(sample, self.settings.server_url.clone()),
                     Message::EditorSession,
-                )
+                );
             }
-            Message::EditorSession((nsample, _path)) => {
+            Message::EditorSession(res) => {
+                let (nsample, _path) = match res {
+                    Err(e) => {
+                        self.status.set(StatusBarLevel::Danger, &e.to_string());
+                        return Task::none();
+                    }
+                    Ok(val) => val,
+                };
                 self.editor.sample = nsample;
-                self.editor.lowpass = None;
-                self.editor.highpass = None;
+                self.editor.reset();
                 self.status
                     .set(StatusBarLevel::Neutral, "Loading editor...");
                 self.view = ViewControl::Editor;
 
                 return Task::perform(
-                    editor::load_editor_audio(self.editor.audio.clone()),
-                    Message::Nothing,
-                )
-                .chain(Task::perform(
-                    waveform::get_waveform_tk(self.editor.audio.clone()),
-                    |val| Message::Editor(EditorEvent::WaveformReloaded(val)),
-                ));
-            }
-            Message::ShuffleResults => match &mut self.results {
-                Some(res) => res.samples.shuffle(&mut thread_rng()),
-                None => (),
-            },
+                    editor::load_editor_audio(self.editor.original.clone()),
+                    |format| Message::Editor(EditorEvent::AudioLoaded(format)),
+                );
+            }
+            // Shuffling only shows in server order, so switch back to it
+            Message::ShuffleResults => {
+                let samples = match (&mut self.local_results, &mut self.results) {
+                    (Some(local), _) => local,
+                    (None, Some(res)) => &mut res.samples,
+                    (None, None) => return Task::none(),
+                };
+                samples.shuffle(&mut thread_rng());
+                self.settings.sort_mode = sorting::SortMode::Server;
+            }
         }
         Task::none()
     }
@@ -0,0 +1,263 @@
+use std::sync::{Arc, RwLock};
+
+use crate::dsp::{self, Edits, Equalizer};
+
+// Past either limit the oldest operations are baked into the base buffer
+const MAX_HISTORY: usize = 200;
+const MAX_REPLAY_PASSES: f32 = 32.0;
+
+// Only the latest Eq and Edits apply, after everything else
+#[derive(Debug, Clone)]
+pub enum Operation {
+    Eq(Equalizer),
+    Edits(Edits),
+    Silence(f32, f32),
+    Reverse,
+    Gain(f32),
+    Pitch(f32),
+}
+impl Operation {
+    fn is_setting(&self) -> bool {
+        matches!(self, Operation::Eq(_) | Operation::Edits(_))
+    }
+    // Rough work of replaying the operation, in passes over the whole buffer
+    fn passes(&self) -> f32 {
+        match self {
+            Operation::Eq(_) | Operation::Edits(_) => 0.0,
+            Operation::Silence(start, end) => (end - start).abs(),
+            Operation::Reverse | Operation::Gain(_) => 1.0,
+            Operation::Pitch(_) => 4.0,
+        }
+    }
+    fn same_kind(&self, other: &Operation) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+    fn apply(&self, samples: &mut Vec<f32>, channels: u16) {
+        match self {
+            Operation::Silence(start, end) => {
+                let (start, end) = dsp::sample_bounds((*start, *end), samples.len(), channels);
+                samples[start..end].iter_mut().for_each(|s| *s = 0.0);
+            }
+            Operation::Reverse => dsp::reverse(samples, channels),
+            Operation::Gain(db) => dsp::apply_gain(samples, *db),
+            Operation::Pitch(semitones) => *samples = dsp::resample(samples, channels, *semitones),
+            Operation::Eq(_) | Operation::Edits(_) => (),
+        }
+    }
+}
+
+pub fn settings(ops: &[Operation]) -> (Equalizer, Edits) {
+    let mut eq = Equalizer::default();
+    let mut edits = Edits::default();
+    for op in ops {
+        match op {
+            Operation::Eq(val) => eq = val.clone(),
+            Operation::Edits(val) => edits = val.clone(),
+            _ => (),
+        }
+    }
+    (eq, edits)
+}
+
+// Name tags for the transformations in a list of operations, like ["rev", "+3st"]
+pub fn name_tags(ops: &[Operation]) -> Vec<String> {
+    let mut reversed = false;
+    let mut gain = 0.0;
+    let mut semitones = 0.0;
+    for op in ops {
+        match op {
+            Operation::Reverse => reversed = !reversed,
+            Operation::Gain(db) => gain += db,
+            Operation::Pitch(val) => semitones += val,
+            _ => (),
+        }
+    }
+    let mut tags = vec![];
+    if reversed {
+        tags.push(String::from("rev"));
+    }
+    let gain: f32 = (gain * 10.0).round() / 10.0;
+    if gain != 0.0 {
+        tags.push(format!("{:+}dB", gain));
+    }
+    let cents = (semitones * 100.0).round() as i32;
+    match (cents / 100, cents % 100) {
+        (0, 0) => (),
+        (st, 0) => tags.push(format!("{:+}st", st)),
+        (0, c) => tags.push(format!("{:+}c", c)),
+        (st, c) => tags.push(format!("{:+}st{:+}c", st, c)),
+    }
+    tags
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EditHistory {
+    pub ops: Vec<Operation>,
+    pub undone: Vec<Operation>,
+}
+impl EditHistory {
+    // Returns the operations that fell out of the history
+    pub fn push(&mut self, op: Operation) -> Vec<Operation> {
+        self.undone.clear();
+        self.ops.push(op);
+        let mut baked = vec![];
+        while self.ops.len() > MAX_HISTORY || self.replay_passes() > MAX_REPLAY_PASSES {
+            // Settings are only dropped once a later operation replaced them
+            let oldest = self.ops.iter().enumerate().position(|(i, op)| {
+                !op.is_setting() || self.ops[i + 1..].iter().any(|later| later.same_kind(op))
+            });
+            match oldest {
+                None => break,
+                Some(i) => {
+                    let op = self.ops.remove(i);
+                    if !op.is_setting() {
+                        baked.push(op);
+                    }
+                }
+            }
+        }
+        baked
+    }
+    fn replay_passes(&self) -> f32 {
+        self.ops.iter().map(|op| op.passes()).sum()
+    }
+    pub fn undo(&mut self) -> bool {
+        match self.ops.pop() {
+            None => false,
+            Some(op) => {
+                self.undone.push(op);
+                true
+            }
+        }
+    }
+    pub fn redo(&mut self) -> bool {
+        match self.undone.pop() {
+            None => false,
+            Some(op) => {
+                self.ops.push(op);
+                true
+            }
+        }
+    }
+}
+
+pub fn bake(base: &Arc<RwLock<Vec<f32>>>, ops: &[Operation], channels: u16) {
+    if let Ok(mut samples) = base.write() {
+        for op in ops {
+            op.apply(&mut samples, channels);
+        }
+    }
+}
+
+pub async fn render(
+    base: Arc<RwLock<Vec<f32>>>,
+    channels: u16,
+    sample_rate: u32,
+    ops: Vec<Operation>,
+) -> Vec<f32> {
+    let mut samples = match base.read() {
+        Err(_) => return vec![],
+        Ok(val) => val.clone(),
+    };
+    for op in &ops {
+        op.apply(&mut samples, channels);
+    }
+    let (eq, edits) = settings(&ops);
+    eq.apply(&mut samples, channels, sample_rate);
+    edits.apply(&mut samples, channels, sample_rate);
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_operation_drops_redo() {
+        let mut history = EditHistory::default();
+        history.push(Operation::Reverse);
+        history.push(Operation::Gain(3.0));
+        assert!(history.undo());
+        assert_eq!(history.undone.len(), 1);
+        history.push(Operation::Silence(0.0, 0.5));
+        assert!(history.undone.is_empty());
+        assert!(!history.redo());
+        assert_eq!(history.ops.len(), 2);
+    }
+
+    #[test]
+    fn undo_and_redo_move_operations() {
+        let mut history = EditHistory::default();
+        assert!(!history.undo());
+        history.push(Operation::Reverse);
+        assert!(history.undo());
+        assert!(history.ops.is_empty());
+        assert!(history.redo());
+        assert!(matches!(history.ops.as_slice(), [Operation::Reverse]));
+    }
+
+    #[test]
+    fn expensive_operations_are_baked() {
+        let mut history = EditHistory::default();
+        let mut baked = vec![];
+        for _ in 0..10 {
+            baked.extend(history.push(Operation::Pitch(1.0)));
+        }
+        assert!(history.replay_passes() <= MAX_REPLAY_PASSES);
+        assert_eq!(history.ops.len() + baked.len(), 10);
+        assert!(baked.iter().all(|op| matches!(op, Operation::Pitch(_))));
+    }
+
+    #[test]
+    fn latest_settings_are_kept_when_baking() {
+        let mut history = EditHistory::default();
+        history.push(Operation::Eq(Equalizer::default()));
+        let mut baked = vec![];
+        for _ in 0..MAX_HISTORY {
+            baked.extend(history.push(Operation::Silence(0.0, 0.0)));
+        }
+        assert_eq!(history.ops.len(), MAX_HISTORY);
+        assert!(matches!(history.ops[0], Operation::Eq(_)));
+        assert_eq!(baked.len(), 1);
+    }
+
+    #[test]
+    fn replaced_settings_are_dropped_without_baking() {
+        let mut history = EditHistory::default();
+        history.push(Operation::Eq(Equalizer::default()));
+        history.push(Operation::Eq(Equalizer::default()));
+        for _ in 0..MAX_HISTORY - 2 {
+            history.push(Operation::Silence(0.0, 0.0));
+        }
+        let baked = history.push(Operation::Silence(0.0, 0.0));
+        assert!(baked.is_empty());
+        assert_eq!(history.ops.len(), MAX_HISTORY);
+        assert_eq!(
+            history
+                .ops
+                .iter()
+                .filter(|op| matches!(op, Operation::Eq(_)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn tags_sum_up_transformations() {
+        let ops = [
+            Operation::Reverse,
+            Operation::Gain(2.0),
+            Operation::Gain(1.0),
+            Operation::Pitch(3.0),
+        ];
+        assert_eq!(name_tags(&ops), vec!["rev", "+3dB", "+3st"]);
+        assert!(name_tags(&[Operation::Reverse, Operation::Reverse]).is_empty());
+    }
+
+    #[test]
+    fn tags_with_cents() {
+        assert_eq!(name_tags(&[Operation::Pitch(-0.2)]), vec!["-20c"]);
+        assert_eq!(name_tags(&[Operation::Pitch(3.8)]), vec!["+3st+80c"]);
+        assert_eq!(name_tags(&[Operation::Pitch(-1.5)]), vec!["-1st-50c"]);
+    }
+}
@@ -25,6 +25,7 @@ pub enum Anchor {
     AboveTop,
     BelowTopCentered,
     BelowBottomCentered,
+    BelowBottomRight,
 }
 
 struct AnchoredOverlay<'a, Message> {
@@ -193,20 +194,19 @@ impl<'a, 'b, Message> overlay::Overlay<Message, Theme, Renderer> for Overlay<'a,
             Anchor::AboveTop => self.position.y,
             // From top of base to bottom of viewport
             Anchor::BelowTopCentered => bounds.height - self.position.y,
-            Anchor::BelowBottomCentered => {
+            Anchor::BelowBottomCentered | Anchor::BelowBottomRight => {
                 bounds.height - self.position.y - self.base_layout.height
             }
         };
+        let width = match self.anchor {
+            // From right edge of base to left of viewport
+            Anchor::BelowBottomRight => self.position.x + self.base_layout.width,
+            _ => self.base_layout.width,
+        };
 
-        let limits = layout::Limits::new(
-            Size::ZERO,
-            Size {
-                width: self.base_layout.width,
-                height,
-            },
-        )
-        .width(Length::Fill)
-        .height(Length::Fill);
+        let limits = layout::Limits::new(Size::ZERO, Size { width, height })
+            .width(Length::Fill)
+            .height(Length::Fill);
 
         let node = self
             .content
@@ -225,6 +225,11 @@ impl<'a, 'b, Message> overlay::Overlay<Message, Theme, Renderer> for Overlay<'a,
                 self.base_layout.width / 2.0 - node.size().width / 2.0,
                 self.offset,
             ),
+            // Right edges aligned, offset below the bottom
+            Anchor::BelowBottomRight => Vector::new(
+                self.base_layout.width - node.size().width,
+                self.base_layout.height + self.offset,
+            ),
         };
 
         node.move_to(self.position + translation)
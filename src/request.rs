@@ -1,109 +1,641 @@
 use audiocloud_lib::*;
-use reqwest::Client;
+use iced::futures::future::join_all;
+use iced::futures::stream::{self, Stream};
+use reqwest::{Client, RequestBuilder, Response, Url};
+use serde_derive::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Notify;
+use xxhash_rust::xxh3::Xxh3;
 
+use crate::cache::{self, CacheEntry};
 use crate::error::*;
 use crate::helpers;
 
-pub async fn check_connection(ip: String) -> bool {
-    let client = Client::new();
-    let response = client.get(ip).send().await;
-    match response {
-        Ok(val) => match val.text().await {
-            Ok(_) => {
-                return true;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+// Servers without paging are never asked for more results than this in one request
+const UNPAGED_LIMIT: i32 = 1000;
+const PACK_PAGE: i32 = 200;
+
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub retries: u32,
+    pub backoff: Duration,
+}
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            timeout: Duration::from_secs(15),
+            connect_timeout: Duration::from_secs(5),
+            retries: 3,
+            backoff: Duration::from_millis(250),
+        }
+    }
+}
+
+// Cheap to clone, all clones share the same connection pool
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    client: Client,
+    config: ApiConfig,
+    // Whether each server honours the search offset
+    paging: Arc<Mutex<HashMap<String, bool>>>,
+}
+
+impl ApiClient {
+    pub fn new(config: ApiConfig) -> Self {
+        // No total timeout on the client, downloads may take longer than any sane API call
+        let client = Client::builder()
+            .read_timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        ApiClient {
+            client,
+            config,
+            paging: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Only used for idempotent requests, retries connection failures, timeouts and 5xx responses
+    async fn send_with_retry(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let err = match request().send().await {
+                Ok(val) if val.status().is_server_error() => Error::new(ErrorType::Server),
+                Ok(val) if !val.status().is_success() => return Err(Error::new(ErrorType::Server)),
+                Ok(val) => return Ok(val),
+                Err(e) if e.is_timeout() => Error::new(ErrorType::Timeout),
+                Err(_) => Error::new(ErrorType::Connection),
+            };
+            if attempt >= self.config.retries {
+                return Err(err);
             }
-            Err(_) => return false,
-        },
-        Err(_) => return false,
-    }
-}
-pub async fn get_result(params: SearchParams, path: String) -> Result<SearchResult, Error> {
-    let client = Client::new();
-    let response_s = match client.post(path + "search").json(&params).send().await {
-        Err(_) => return Err(Error::new(ErrorType::Connection)),
-        Ok(val) => val,
-    }
-    .text()
-    .await;
-    let response = match response_s {
-        Err(_) => return Err(Error::new(ErrorType::Parse)),
-        Ok(val) => val,
-    };
+            tokio::time::sleep(self.config.backoff * 2u32.pow(attempt)).await;
+            attempt += 1;
+        }
+    }
 
-    let out: SearchResult = match serde_json::from_str(&response) {
-        Err(_) => return Err(Error::new(ErrorType::JSON)),
-        Ok(val) => val,
-    };
-    Ok(out)
-}
-pub async fn get_editor_audio(sample: Sample, server_url: String) -> (Sample, String) {
-    let file_path = sample.path.clone();
-    let tempaudio_path = "editor.wav";
-    let client = Client::new();
-    let file_path_web = file_path.replace("#", "%23").replace(" ", "%20");
-    let url = server_url + "samples/" + &file_path_web;
-    let response = client
-        .get(url)
-        .send()
-        .await
-        .expect("Couldnt send file get request");
-    let body = response.bytes().await.expect("body invalid");
-    let _ = std::fs::write(tempaudio_path, &body);
-    (sample, String::from(tempaudio_path))
-}
-
-pub async fn get_temp_audio(server_url: String, file_path: String) -> String {
-    let tempaudio_path = "Tempaudio.wav";
-    let client = Client::new();
-    let file_path_web = file_path.replace("#", "%23").replace(" ", "%20");
-    let url = server_url + "samples/" + &file_path_web;
-    let response = client
-        .get(url)
-        .send()
+    async fn get_with_retry(&self, url: Url, timeout: Option<Duration>) -> Result<Response, Error> {
+        self.send_with_retry(|| {
+            let request = self.client.get(url.clone());
+            match timeout {
+                Some(timeout) => request.timeout(timeout),
+                None => request,
+            }
+        })
         .await
-        .expect("Couldnt send file get request");
-    let body = response.bytes().await.expect("body invalid");
-    let _ = std::fs::write(tempaudio_path, &body);
-    String::from(tempaudio_path)
-}
-
-pub async fn get_packs_meta(server_url: String) -> Result<Vec<PackInfo>, Error> {
-    let client = Client::new();
-    let url = server_url + "packs";
-    let response_s: reqwest::Response = match client.get(url).send().await {
-        Err(_) => return Err(Error::new(ErrorType::Connection)),
-        Ok(val) => val,
-    };
+    }
 
-    let response = match response_s.text().await {
-        Err(_) => return Err(Error::new(ErrorType::Parse)),
-        Ok(val) => val,
-    };
-    let out: Vec<PackInfo> = match serde_json::from_str(&response) {
-        Err(_) => return Err(Error::new(ErrorType::JSON)),
-        Ok(val) => val,
-    };
-    Ok(out)
+    async fn get_bytes(&self, url: Url) -> Result<Vec<u8>, Error> {
+        let response = self.get_with_retry(url, Some(self.config.timeout)).await?;
+        match response.bytes().await {
+            Err(e) if e.is_timeout() => Err(Error::new(ErrorType::Timeout)),
+            Err(_) => Err(Error::new(ErrorType::Connection)),
+            Ok(val) => Ok(val.to_vec()),
+        }
+    }
+
+    pub async fn check_connection(self, ip: String) -> bool {
+        match self
+            .client
+            .get(ip)
+            .timeout(self.config.timeout)
+            .send()
+            .await
+        {
+            Ok(val) => val.text().await.is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    async fn post_search(
+        self,
+        params: &SearchParams,
+        offset: Option<usize>,
+        path: &str,
+    ) -> Result<SearchResult, Error> {
+        let url = api_url(path, "search")?;
+        let response_s = self
+            .send_with_retry(|| {
+                self.client
+                    .post(url.clone())
+                    .timeout(self.config.timeout)
+                    .json(&PagedParams { params, offset })
+            })
+            .await?
+            .text()
+            .await;
+        let response = match response_s {
+            Err(_) => return Err(Error::new(ErrorType::Parse)),
+            Ok(val) => val,
+        };
+
+        let out: SearchResult = match serde_json::from_str(&response) {
+            Err(_) => return Err(Error::new(ErrorType::JSON)),
+            Ok(val) => val,
+        };
+        Ok(out)
+    }
+
+    pub async fn get_first_page(
+        self,
+        params: SearchParams,
+        path: String,
+    ) -> Result<SearchPage, Error> {
+        let res = self.post_search(&params, None, &path).await?;
+        Ok(SearchPage::new(res, &params))
+    }
+
+    // Servers without paging send the first page again, it is cut out of a larger request then
+    pub async fn get_result_page(
+        self,
+        params: SearchParams,
+        offset: usize,
+        seen: Vec<String>,
+        path: String,
+    ) -> Result<SearchPage, Error> {
+        let server = helpers::server_id(&path);
+        let paging = self
+            .paging
+            .lock()
+            .ok()
+            .and_then(|p| p.get(&server).copied());
+        if paging != Some(false) {
+            let res = self
+                .clone()
+                .post_search(&params, Some(offset), &path)
+                .await?;
+            let repeated = res.samples.iter().any(|s| seen.contains(&s.path));
+            if !seen.is_empty() && !res.samples.is_empty() {
+                if let Ok(mut paging) = self.paging.lock() {
+                    paging.insert(server, !repeated);
+                }
+            }
+            if !repeated {
+                return Ok(SearchPage::new(res, &params));
+            }
+        }
+        let end = params
+            .max_results
+            .map(|page| (page + offset as i32).min(UNPAGED_LIMIT));
+        let mut larger = params.clone();
+        larger.max_results = end;
+        let mut res = self.post_search(&larger, None, &path).await?;
+        res.samples = res.samples.into_iter().skip(offset).collect();
+        let mut page = SearchPage::new(res, &params);
+        page.has_more &= end.is_some_and(|end| end < UNPAGED_LIMIT);
+        Ok(page)
+    }
+
+    // The server only filters by a single pack, so each one is queried and merged
+    pub async fn get_result_packs(
+        self,
+        params: SearchParams,
+        packs: Vec<String>,
+        offset: usize,
+        seen: Vec<String>,
+        path: String,
+    ) -> Result<SearchPage, Error> {
+        let pages = join_all(packs.into_iter().map(|pack| {
+            let mut pack_params = params.clone();
+            pack_params.pack_id = Some(pack);
+            self.clone()
+                .get_result_page(pack_params, offset, seen.clone(), path.clone())
+        }))
+        .await;
+        let mut out: Option<SearchPage> = None;
+        for page in pages {
+            let page = page?;
+            match &mut out {
+                None => out = Some(page),
+                Some(val) => {
+                    val.result.samples.extend(page.result.samples);
+                    val.has_more |= page.has_more;
+                }
+            }
+        }
+        match out {
+            None => self.get_first_page(params, path).await,
+            Some(val) => Ok(val),
+        }
+    }
+
+    // Lists a whole pack with an empty query, page by page
+    pub async fn get_pack_samples(
+        self,
+        pack_id: String,
+        server_url: String,
+    ) -> Result<Vec<Sample>, Error> {
+        let params = SearchParams {
+            query: String::new(),
+            sample_type: None,
+            max_tempo: None,
+            min_tempo: None,
+            pack_id: Some(pack_id),
+            max_results: Some(PACK_PAGE),
+        };
+        let mut page = self
+            .clone()
+            .get_first_page(params.clone(), server_url.clone())
+            .await?;
+        let mut samples: Vec<Sample> = vec![];
+        let mut seen: HashSet<String> = HashSet::new();
+        loop {
+            let before = samples.len();
+            for sample in page.result.samples {
+                if seen.insert(sample.path.clone()) {
+                    samples.push(sample);
+                }
+            }
+            if !page.has_more || samples.len() == before {
+                return Ok(samples);
+            }
+            page = self
+                .clone()
+                .get_result_page(
+                    params.clone(),
+                    samples.len(),
+                    seen.iter().cloned().collect(),
+                    server_url.clone(),
+                )
+                .await?;
+        }
+    }
+
+    pub async fn get_editor_audio(
+        self,
+        sample: Sample,
+        server_url: String,
+    ) -> Result<(Sample, String), Error> {
+        let tempaudio_path = "editor.wav";
+        let url = sample_url(&server_url, &sample.path)?;
+        let body = self.get_bytes(url).await?;
+        if std::fs::write(tempaudio_path, &body).is_err() {
+            return Err(Error::new(ErrorType::FileSave));
+        }
+        Ok((sample, String::from(tempaudio_path)))
+    }
+
+    pub async fn get_temp_audio(
+        self,
+        server_url: String,
+        file_path: String,
+    ) -> Result<String, Error> {
+        let tempaudio_path = cache::preview_path(&server_url, &file_path);
+        if std::path::Path::new(&tempaudio_path).exists() {
+            return Ok(tempaudio_path);
+        }
+        let url = sample_url(&server_url, &file_path)?;
+        let body = self.get_bytes(url).await?;
+        let part_path = tempaudio_path.clone() + ".part";
+        if std::fs::write(&part_path, &body).is_err()
+            || std::fs::rename(&part_path, &tempaudio_path).is_err()
+        {
+            return Err(Error::new(ErrorType::FileSave));
+        }
+        cache::prune_previews();
+        Ok(tempaudio_path)
+    }
+
+    pub async fn get_packs_meta(self, server_url: String) -> Result<Vec<PackInfo>, Error> {
+        let url = api_url(&server_url, "packs")?;
+        let response_s = self.get_with_retry(url, Some(self.config.timeout)).await?;
+
+        let response = match response_s.text().await {
+            Err(_) => return Err(Error::new(ErrorType::Parse)),
+            Ok(val) => val,
+        };
+        let out: Vec<PackInfo> = match serde_json::from_str(&response) {
+            Err(_) => return Err(Error::new(ErrorType::JSON)),
+            Ok(val) => val,
+        };
+        Ok(out)
+    }
+
+    // Streams into a ".part" file that is renamed to its content hash once complete
+    pub fn download_stream(
+        self,
+        server_url: String,
+        file_path: String,
+        cancel: Arc<Notify>,
+    ) -> impl Stream<Item = DownloadProgress> {
+        stream::unfold(DownloadStep::Start, move |step| {
+            let api = self.clone();
+            let server_url = server_url.clone();
+            let file_path = file_path.clone();
+            let cancel = cancel.clone();
+            async move {
+                match step {
+                    DownloadStep::Start => {
+                        let started = tokio::select! {
+                            _ = cancel.notified() => Err(Error::new(ErrorType::Cancelled)),
+                            res = api.start_download(&server_url, &file_path) => res,
+                        };
+                        match started {
+                            Err(e) => {
+                                Some((DownloadProgress::Finished(Err(e)), DownloadStep::Done))
+                            }
+                            Ok(state) => Some((
+                                DownloadProgress::Progress(0, state.total),
+                                DownloadStep::Streaming(state),
+                            )),
+                        }
+                    }
+                    DownloadStep::Streaming(state) => Some(next_chunks(state, &cancel).await),
+                    DownloadStep::Done => None,
+                }
+            }
+        })
+    }
+
+    async fn start_download(
+        &self,
+        server_url: &str,
+        file_path: &str,
+    ) -> Result<DownloadState, Error> {
+        if !std::path::Path::new(cache::CACHE_DIR).exists() {
+            let _ = fs::create_dir(cache::CACHE_DIR);
+        }
+        let part_path = cache::part_path(server_url, file_path);
+        let url = sample_url(server_url, file_path)?;
+        let response = self.get_with_retry(url, None).await?;
+        let file = match tokio::fs::File::create(&part_path).await {
+            Err(_) => return Err(Error::new(ErrorType::FileSave)),
+            Ok(val) => val,
+        };
+        Ok(DownloadState {
+            last_report: Instant::now(),
+            total: response.content_length(),
+            response,
+            file,
+            downloaded: 0,
+            hasher: Xxh3::new(),
+            part_path,
+            server: helpers::server_id(server_url),
+            file_path: file_path.to_string(),
+        })
+    }
 }
 
-pub async fn dl_sample(server_url: String, file_path: String) -> String {
-    if !std::path::Path::new("cached/").exists() {
-        let _ = fs::create_dir("cached/");
+#[derive(Debug, Clone)]
+pub struct SearchPage {
+    pub result: SearchResult,
+    pub has_more: bool,
+}
+impl SearchPage {
+    fn new(result: SearchResult, params: &SearchParams) -> Self {
+        let has_more = params
+            .max_results
+            .is_some_and(|max| result.samples.len() >= max as usize);
+        SearchPage { result, has_more }
     }
-    let folder_path = "cached/".to_string();
-    let path = folder_path + &helpers::hash_sample(&file_path.replace(".wav", "")) + ".wav";
-    let client = Client::new();
-    let file_path_web = file_path.replace("#", "%23").replace(" ", "%20");
-    let url = server_url + "samples/" + &file_path_web;
-    let response = client
-        .get(url)
-        .send()
+}
+
+#[derive(Debug, Clone)]
+pub enum DownloadProgress {
+    // Bytes written so far and the total size if the server sent one
+    Progress(u64, Option<u64>),
+    Finished(Result<CacheEntry, Error>),
+}
+
+struct DownloadState {
+    last_report: Instant,
+    response: Response,
+    file: tokio::fs::File,
+    downloaded: u64,
+    total: Option<u64>,
+    hasher: Xxh3,
+    part_path: String,
+    server: String,
+    file_path: String,
+}
+
+enum DownloadStep {
+    Start,
+    Streaming(DownloadState),
+    Done,
+}
+
+// Reads chunks until PROGRESS_INTERVAL passed
+async fn next_chunks(
+    mut state: DownloadState,
+    cancel: &Notify,
+) -> (DownloadProgress, DownloadStep) {
+    loop {
+        let chunk = tokio::select! {
+            _ = cancel.notified() => {
+                let _ = state.file.flush().await;
+                drop(state.file);
+                let _ = tokio::fs::remove_file(&state.part_path).await;
+                let err = Error::new(ErrorType::Cancelled);
+                return (DownloadProgress::Finished(Err(err)), DownloadStep::Done);
+            }
+            res = state.response.chunk() => res,
+        };
+        match chunk {
+            Ok(Some(chunk)) => {
+                if state.file.write_all(&chunk).await.is_err() {
+                    let _ = tokio::fs::remove_file(&state.part_path).await;
+                    let err = Error::new(ErrorType::FileSave);
+                    return (DownloadProgress::Finished(Err(err)), DownloadStep::Done);
+                }
+                state.hasher.update(&chunk);
+                state.downloaded += chunk.len() as u64;
+                if state.last_report.elapsed() >= PROGRESS_INTERVAL {
+                    state.last_report = Instant::now();
+                    return (
+                        DownloadProgress::Progress(state.downloaded, state.total),
+                        DownloadStep::Streaming(state),
+                    );
+                }
+            }
+            Ok(None) => {
+                let res = finish_download(state).await;
+                return (DownloadProgress::Finished(res), DownloadStep::Done);
+            }
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&state.part_path).await;
+                let err = match e.is_timeout() {
+                    true => Error::new(ErrorType::Timeout),
+                    false => Error::new(ErrorType::Connection),
+                };
+                return (DownloadProgress::Finished(Err(err)), DownloadStep::Done);
+            }
+        }
+    }
+}
+
+async fn finish_download(mut state: DownloadState) -> Result<CacheEntry, Error> {
+    if state.file.flush().await.is_err() {
+        let _ = tokio::fs::remove_file(&state.part_path).await;
+        return Err(Error::new(ErrorType::FileSave));
+    }
+    drop(state.file);
+
+    let content_hash = format!("{:016x}", state.hasher.digest());
+    let original_name = state
+        .file_path
+        .rsplit('/')
+        .next()
+        .unwrap_or(&state.file_path)
+        .to_string();
+    let extension = match std::path::Path::new(&original_name).extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase(),
+        None => String::from("wav"),
+    };
+    let file = content_hash.clone() + "." + &extension;
+    let target_path = cache::CACHE_DIR.to_string() + &file;
+
+    // Identical content is already on disk, keep the existing file
+    if std::path::Path::new(&target_path).exists() {
+        let _ = tokio::fs::remove_file(&state.part_path).await;
+    } else if tokio::fs::rename(&state.part_path, &target_path)
         .await
-        .expect("Couldnt send file get request");
-    let body = response.bytes().await.expect("body invalid");
-    let _ = std::fs::write(path, &body);
-    file_path
+        .is_err()
+    {
+        return Err(Error::new(ErrorType::FileSave));
+    }
+    Ok(CacheEntry {
+        server: state.server,
+        path: state.file_path,
+        file,
+        original_name,
+        size: state.downloaded,
+        content_hash,
+        last_access: cache::now(),
+        sampletype: None,
+    })
+}
+
+// Keeps only RFC 3986 unreserved characters, everything else is percent-encoded as UTF-8
+pub fn encode_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn base_url(server_url: &str) -> Result<Url, Error> {
+    let mut base = String::from(server_url.trim());
+    if !base.ends_with('/') {
+        base.push('/');
+    }
+    Url::parse(&base).map_err(|_| Error::new(ErrorType::Url))
+}
+
+pub fn api_url(server_url: &str, endpoint: &str) -> Result<Url, Error> {
+    base_url(server_url)?
+        .join(&encode_segment(endpoint))
+        .map_err(|_| Error::new(ErrorType::Url))
+}
+
+// Segments between '/' or '\' are encoded on their own, dot segments are dropped
+pub fn sample_url(server_url: &str, sample_path: &str) -> Result<Url, Error> {
+    let mut relative = String::from("samples");
+    let segments = sample_path
+        .split(['/', '\\'])
+        .filter(|s| !s.is_empty() && *s != "." && *s != "..");
+    for segment in segments {
+        relative.push('/');
+        relative.push_str(&encode_segment(segment));
+    }
+    base_url(server_url)?
+        .join(&relative)
+        .map_err(|_| Error::new(ErrorType::Url))
+}
+
+#[derive(Serialize)]
+struct PagedParams<'a> {
+    #[serde(flatten)]
+    params: &'a SearchParams,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
 }
 
 pub async fn nothing() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: &str = "http://127.0.0.1:4040/";
+
+    #[test]
+    fn plain_path() {
+        let url = sample_url(SERVER, "Drums/kick.wav").unwrap();
+        assert_eq!(url.as_str(), "http://127.0.0.1:4040/samples/Drums/kick.wav");
+    }
+
+    #[test]
+    fn base_without_trailing_slash() {
+        let url = sample_url("http://127.0.0.1:4040", "kick.wav").unwrap();
+        assert_eq!(url.as_str(), "http://127.0.0.1:4040/samples/kick.wav");
+        let url = api_url("http://host:4040/api", "search").unwrap();
+        assert_eq!(url.as_str(), "http://host:4040/api/search");
+    }
+
+    #[test]
+    fn reserved_characters() {
+        let url = sample_url(SERVER, "Loops/120 #1 ?mix% & more+.wav").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:4040/samples/Loops/120%20%231%20%3Fmix%25%20%26%20more%2B.wav"
+        );
+    }
+
+    #[test]
+    fn brackets() {
+        let url = sample_url(SERVER, "Pack (2024)/Snare [Processed] {Wet}.wav").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:4040/samples/Pack%20%282024%29/Snare%20%5BProcessed%5D%20%7BWet%7D.wav"
+        );
+    }
+
+    #[test]
+    fn unicode() {
+        let url = sample_url(SERVER, "Vocals/Café_Ünïcode_声.wav").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:4040/samples/Vocals/Caf%C3%A9_%C3%9Cn%C3%AFcode_%E5%A3%B0.wav"
+        );
+    }
+
+    #[test]
+    fn windows_separators() {
+        let url = sample_url(SERVER, "Drums\\Kicks\\kick 1.wav").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:4040/samples/Drums/Kicks/kick%201.wav"
+        );
+        let url = sample_url(SERVER, "..\\settings.json").unwrap();
+        assert!(url.path().starts_with("/samples/"));
+    }
+
+    #[test]
+    fn dot_segments_stay_inside_samples() {
+        let url = sample_url(SERVER, "../settings.json").unwrap();
+        assert!(url.path().starts_with("/samples/"));
+    }
+
+    #[test]
+    fn invalid_server_url() {
+        assert!(sample_url("not a url", "kick.wav").is_err());
+    }
+}
@@ -1,23 +1,55 @@
-use audiocloud_lib::PackInfo;
+use audiocloud_lib::{PackInfo, Sample};
+use iced::widget::scrollable::RelativeOffset;
 use iced::widget::tooltip::Position;
 use iced::widget::{
-    button, checkbox, column, container, horizontal_space, overlay, row, scrollable, stack, text,
-    text_input, tooltip, vertical_space,
+    button, checkbox, column, container, horizontal_space, overlay, pick_list, row, scrollable,
+    stack, text, text_input, tooltip, vertical_space,
 };
 use iced::{alignment, Alignment, Element, Length, Padding, Subscription, Task, Theme};
 
+use crate::downloads::{downloads_panel, BatchSource, DownloadEvent};
+use crate::history::{self, SavedSearch};
+use crate::keymap::Action;
+use crate::library;
+use crate::local::SearchSource;
 use crate::settings::SearchViewTitle;
+use crate::sorting::{self, GroupMode, SortMode};
 use crate::{bootstrap::*, request, ICON_FONT};
 use crate::{helpers, themes, widgets, AudioCloud, Message, SampleType};
 use crate::{overlay_anchor::anchored_overlay, widgets::*};
 
+pub const SEARCH_INPUT_ID: &str = "search-input";
+const RESULTS_SCROLL_ID: &str = "search-results";
+
 pub fn searchview(app: &AudioCloud) -> Element<Message> {
     let status_text = app.status.statusbar_text();
     let settings = button(text(icon_to_string(Bootstrap::GearFill)).font(ICON_FONT))
         .on_press(Message::SettingsButtonToggled)
         .padding([5, 10]);
+    let downloads_label = match app.downloads.active() {
+        0 => row![text(icon_to_string(Bootstrap::Download)).font(ICON_FONT)],
+        n => row![
+            text(icon_to_string(Bootstrap::Download)).font(ICON_FONT),
+            text(n)
+        ]
+        .spacing(5),
+    };
+    let downloads_button = button(downloads_label)
+        .style(button::secondary)
+        .on_press(Message::Download(DownloadEvent::TogglePanel))
+        .padding([5, 10]);
+    let downloads = if app.downloads.show_panel {
+        container(anchored_overlay(
+            downloads_button,
+            downloads_panel(app),
+            crate::overlay_anchor::Anchor::BelowBottomRight,
+            10.0,
+        ))
+    } else {
+        container(downloads_button)
+    };
     let status_bar = container(
-        row![horizontal_space(), status_text, settings]
+        row![horizontal_space(), status_text, downloads, settings]
             .spacing(10)
             .align_y(Alignment::Center),
     );
@@ -37,6 +69,7 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
     };
 
     let input_text = text_input("Some query...", &app.input)
+        .id(text_input::Id::new(SEARCH_INPUT_ID))
         .on_input(Message::InputChanged)
         .on_submit(Message::CreateTask)
         .width(Length::FillPortion(6))
@@ -48,15 +81,38 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
     } else {
         container::transparent
     };
-    let input = container(input_text)
-        .padding(Padding {
-            top: 5.0,
-            bottom: 0.0,
-            left: 50.0,
-            right: 50.0,
-        })
-        .style(inputstyle)
-        .align_y(Alignment::Center);
+    let history_button = button(
+        text(icon_to_string(Bootstrap::ClockHistory))
+            .font(ICON_FONT)
+            .size(22),
+    )
+    .style(button::text)
+    .on_press(Message::SearchView(SearchView::HistoryOverlay));
+    let history_dropdown = if app.search_view_state.show_history_overlay {
+        container(anchored_overlay(
+            history_button,
+            history::history_dropdown(app),
+            crate::overlay_anchor::Anchor::BelowBottomRight,
+            10.0,
+        ))
+    } else {
+        container(history_button)
+    };
+    let input = container(
+        column![
+            row![input_text, history_dropdown].align_y(Alignment::Center),
+            history::saved_chips(app)
+        ]
+        .spacing(5),
+    )
+    .padding(Padding {
+        top: 5.0,
+        bottom: 0.0,
+        left: 50.0,
+        right: 50.0,
+    })
+    .style(inputstyle)
+    .align_y(Alignment::Center);
 
     let fav_all_text = match app.search_options.show_all_favourites {
         true => text(icon_to_string(Bootstrap::StarFill)).style(|theme: &Theme| text::Style {
@@ -87,7 +143,55 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
     .gap(10)
     .style(container::rounded_box);
 
-    let tempo_filter_button = button(text("Tempo")).style(button::secondary);
+    let download_results = tooltip(
+        button(
+            text(icon_to_string(Bootstrap::CloudDownload))
+                .font(ICON_FONT)
+                .size(22),
+        )
+        .style(button::text)
+        .on_press(Message::Download(DownloadEvent::Batch(
+            BatchSource::Results,
+        ))),
+        text("Download all results"),
+        Position::Left,
+    )
+    .gap(10)
+    .style(container::rounded_box);
+    let download_favourites = tooltip(
+        button(
+            text(icon_to_string(Bootstrap::CloudArrowDownFill))
+                .font(ICON_FONT)
+                .size(22),
+        )
+        .style(button::text)
+        .on_press(Message::Download(DownloadEvent::Batch(
+            BatchSource::Favourites,
+        ))),
+        text("Download all favourites"),
+        Position::Left,
+    )
+    .gap(10)
+    .style(container::rounded_box);
+
+    let tempo_label = row![
+        text(icon_to_string(Bootstrap::Speedometer)).font(ICON_FONT),
+        text(app.search_options.tempo_label())
+    ]
+    .spacing(10);
+    let tempo_filter_button = button(tempo_label)
+        .style(button::secondary)
+        .on_press(Message::SearchView(SearchView::TempoOverlay));
+    let tempofilter = if app.search_view_state.show_tempo_overlay {
+        container(anchored_overlay(
+            tempo_filter_button,
+            tempo_selector(app),
+            crate::overlay_anchor::Anchor::BelowTopCentered,
+            10.0,
+        ))
+    } else {
+        container(tempo_filter_button)
+    };
 
     let pack_label = row![
         text(icon_to_string(Bootstrap::BoxSeam)).font(ICON_FONT),
@@ -100,7 +204,7 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
     let packfilter = if app.search_view_state.show_pack_overlay {
         container(anchored_overlay(
             pack_filter_button,
-            pack_selector(&app.pack_meta),
+            pack_selector(&app.pack_meta, &app.search_view_state.pack_ids),
             crate::overlay_anchor::Anchor::BelowTopCentered,
             10.0,
         ))
@@ -119,9 +223,23 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
             .on_toggle(Message::ShowOnlyFavouritesToggled)
             .size(22)
             .style(checkbox::success),
-        tempo_filter_button,
+        pick_list(
+            SearchSource::all(),
+            Some(app.search_options.source),
+            |source| Message::SearchView(SearchView::Source(source))
+        ),
+        tempofilter,
         packfilter,
+        pack_chips(&app.pack_meta, &app.search_view_state.pack_ids),
         horizontal_space(),
+        pick_list(SortMode::all(), Some(app.settings.sort_mode), |mode| {
+            Message::SearchView(SearchView::Sort(mode))
+        }),
+        pick_list(GroupMode::all(), Some(app.settings.group_mode), |mode| {
+            Message::SearchView(SearchView::Group(mode))
+        }),
+        download_results,
+        download_favourites,
         shuffle_order,
         fav_all
     ]
@@ -134,7 +252,29 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
     })
     .spacing(15);
 
-    let filter_label = container(text("Filters").size(22)).padding(Padding {
+    let result_count = match listed_samples(app) {
+        None => String::new(),
+        Some(samples) => {
+            let loaded = samples.len();
+            let more = match app.search_view_state.has_more {
+                true => "+",
+                false => "",
+            };
+            match visible_samples(app).len() {
+                shown if shown == loaded => format!("{}{} results", loaded, more),
+                shown => format!("{}{} results, {} shown", loaded, more, shown),
+            }
+        }
+    };
+    let filter_label = container(
+        row![
+            text("Filters").size(22),
+            horizontal_space(),
+            text(result_count).style(themes::text_fg)
+        ]
+        .align_y(Alignment::Center),
+    )
+    .padding(Padding {
         top: 0.0,
         right: 20.0,
         bottom: 0.0,
@@ -143,15 +283,23 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
     let filters = container(column![filter_label, sample_type_selector]).padding(10);
 
     let mut result_row = column![];
-    match &app.results {
-        Some(val) => {
-            let samples = match app.search_options.show_all_favourites {
-                true => &app.settings.favourite_samples,
-                false => &val.samples,
-            };
-            for sample in samples {
-                if app.search_options.show_only_favourites && !app.settings.is_favourite(sample) {
-                    continue;
+    match listed_samples(app) {
+        Some(_) => {
+            let mut last_group = None;
+            for sample in visible_samples(app) {
+                let group = sorting::group_key(app.settings.group_mode, sample, &app.pack_meta);
+                if group.is_some() && group != last_group {
+                    result_row = result_row.push(
+                        container(text(group.clone().unwrap_or_default()).size(20)).padding(
+                            Padding {
+                                top: 10.0,
+                                right: 20.0,
+                                bottom: 0.0,
+                                left: 20.0,
+                            },
+                        ),
+                    );
+                    last_group = group;
                 }
                 let name =
                     helpers::remove_brackets(&sample.name.replace(".wav", "").replace("_", " "));
@@ -196,7 +344,10 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
                     .style(button::text)
                     .on_press(Message::ToggleFavourite(sample.clone()));
 
-                let dl_text = match app.settings.is_downloaded(&sample.path) {
+                // Library samples are already on disk and can be dragged right away
+                let on_disk = app.cache.contains(&app.settings.server_url, &sample.path)
+                    || library::is_library_path(&sample.path);
+                let dl_text = match on_disk {
                     false => text(icon_to_string(Bootstrap::Download)).style(|theme: &Theme| {
                         text::Style {
                             color: Some(theme.extended_palette().primary.strong.color),
@@ -210,10 +361,10 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
                         })
                     }
                 };
-                let dl_button = match app.settings.is_downloaded(&sample.path) {
+                let dl_button = match on_disk {
                     false => button(dl_text.font(ICON_FONT).size(20))
                         .style(button::text)
-                        .on_press(Message::DownloadSample(sample.path.clone())),
+                        .on_press(Message::DownloadSample(sample.clone())),
                     true => button(dl_text.font(ICON_FONT).size(20))
                         .style(button::text)
                         .on_press(Message::DragSample(sample.path.clone())),
@@ -228,10 +379,7 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
                         )
                         .style(|theme, status| button::text(theme, status))
                         .padding(20)
-                        .on_press(Message::PlaySample(
-                            sample.path.to_string(),
-                            sample.name.clone()
-                        )),
+                        .on_press(Message::PlaySample(sample.clone())),
                         column![text(name).size(25), type_label],
                         horizontal_space(),
                         dl_button,
@@ -240,8 +388,22 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
                     ]
                     .align_y(Alignment::Center),
                 );
+                let sample_entry =
+                    match app.search_view_state.selected.as_ref() == Some(&sample.path) {
+                        true => sample_entry.style(themes::container_front),
+                        false => sample_entry,
+                    };
                 result_row = result_row.push(sample_entry.align_y(alignment::Vertical::Center));
             }
+            if app.search_view_state.loading_more {
+                result_row = result_row.push(text("Loading...").style(themes::text_fg));
+            } else if app.search_view_state.has_more {
+                result_row = result_row.push(
+                    button(text("Load more"))
+                        .style(button::text)
+                        .on_press(Message::LoadMore),
+                );
+            }
         }
         None => {
             let no_samples_text = container(text("No results").size(30));
@@ -251,7 +413,13 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
 
     result_row = result_row.spacing(5);
     let result_scollable = container(
-        scrollable(result_row).style(|theme, status| themes::scrollbar_invis(theme, status)),
+        scrollable(result_row)
+            .id(scrollable::Id::new(RESULTS_SCROLL_ID))
+            .on_scroll(|viewport| match viewport.relative_offset().y > 0.95 {
+                true => Message::LoadMore,
+                false => Message::Nothing(()),
+            })
+            .style(|theme, status| themes::scrollbar_invis(theme, status)),
     )
     .padding(widgets::padding_now(10));
 
@@ -272,10 +440,132 @@ pub fn searchview(app: &AudioCloud) -> Element<Message> {
     .into()
 }
 
+// Local results replace the server ones while searching locally or offline
+fn listed_samples(app: &AudioCloud) -> Option<&Vec<Sample>> {
+    match (&app.local_results, &app.results) {
+        (Some(local), _) => Some(local),
+        (None, Some(val)) => Some(&val.samples),
+        (None, None) => None,
+    }
+}
+
+fn shown_samples(app: &AudioCloud) -> Option<&Vec<Sample>> {
+    match app.search_options.show_all_favourites {
+        true => listed_samples(app).map(|_| &app.settings.favourite_samples),
+        false => listed_samples(app),
+    }
+}
+
+// The order is worked out by refresh_order, views and key presses only read it
+pub fn visible_samples(app: &AudioCloud) -> Vec<&Sample> {
+    let samples = match shown_samples(app) {
+        None => return vec![],
+        Some(val) => val,
+    };
+    app.search_view_state
+        .order
+        .iter()
+        .filter_map(|i| samples.get(*i))
+        .collect()
+}
+
+pub fn refresh_order(app: &mut AudioCloud) {
+    let order = match shown_samples(app) {
+        None => vec![],
+        Some(samples) => {
+            let mut samples: Vec<(usize, &Sample)> = samples
+                .iter()
+                .enumerate()
+                .filter(|(_, sample)| {
+                    !app.search_options.show_only_favourites || app.settings.is_favourite(sample)
+                })
+                .filter(|(_, sample)| app.search_options.tempo_matches(&sample.sampletype))
+                .collect();
+            sorting::sort_samples(app, &mut samples);
+            samples.into_iter().map(|(i, _)| i).collect()
+        }
+    };
+    app.search_view_state.order = order;
+}
+
+// Keyboard actions on the result list, keys typed into the search box never get here
+pub fn result_action(app: &mut AudioCloud, action: Action) -> Task<Message> {
+    let selected = app.search_view_state.selected.as_ref().and_then(|path| {
+        visible_samples(app)
+            .into_iter()
+            .find(|s| &s.path == path)
+            .cloned()
+    });
+    let message = match (action, selected) {
+        (Action::NextResult, _) => return move_selection(app, 1),
+        (Action::PreviousResult, _) => return move_selection(app, -1),
+        (Action::FocusSearch, _) => return text_input::focus(text_input::Id::new(SEARCH_INPUT_ID)),
+        (Action::Favourite, Some(sample)) => Message::ToggleFavourite(sample),
+        (Action::Download, Some(sample))
+            if !app.cache.contains(&app.settings.server_url, &sample.path)
+                && !library::is_library_path(&sample.path) =>
+        {
+            Message::DownloadSample(sample)
+        }
+        (Action::OpenEditor, Some(sample)) => Message::EditorSessionDL(sample),
+        _ => return Task::none(),
+    };
+    Task::perform(request::nothing(), move |()| message)
+}
+
+fn move_selection(app: &mut AudioCloud, delta: i32) -> Task<Message> {
+    let samples = visible_samples(app);
+    if samples.is_empty() {
+        return Task::none();
+    }
+    let last = samples.len() - 1;
+    let current = app
+        .search_view_state
+        .selected
+        .as_ref()
+        .and_then(|path| samples.iter().position(|s| &s.path == path));
+    let index = match current {
+        None => 0,
+        Some(i) => (i as i32 + delta).clamp(0, last as i32) as usize,
+    };
+    if current == Some(index) {
+        return Task::none();
+    }
+    let sample = samples[index].clone();
+    app.search_view_state.selected = Some(sample.path.clone());
+    let offset = match last {
+        0 => 0.0,
+        _ => index as f32 / last as f32,
+    };
+    Task::batch([
+        scrollable::snap_to(
+            scrollable::Id::new(RESULTS_SCROLL_ID),
+            RelativeOffset { x: 0.0, y: offset },
+        ),
+        Task::perform(request::nothing(), move |()| Message::PlaySample(sample)),
+    ])
+}
+
 pub fn search_update(message: SearchView, app: &mut AudioCloud) -> Task<Message> {
     match message {
         SearchView::PackID(id) => {
-            app.search_view_state.pack_id = id;
+            match id {
+                None => app.search_view_state.pack_ids.clear(),
+                Some(id) => {
+                    if let Some(pos) = app.search_view_state.pack_ids.iter().position(|p| p == &id)
+                    {
+                        app.search_view_state.pack_ids.remove(pos);
+                    } else {
+                        app.search_view_state.pack_ids.push(id);
+                    }
+                }
+            }
+            app.search_view_state.show_pack_overlay = false;
+            return app.create_request_command(app.input.clone());
+        }
+        SearchView::RemovePack(id) => {
+            app.search_view_state.pack_ids.retain(|p| p != &id);
+            return app.create_request_command(app.input.clone());
         }
         SearchView::PackOverlay => {
             app.search_view_state.show_pack_overlay = !app.search_view_state.show_pack_overlay;
@@ -285,11 +575,83 @@ pub fn search_update(message: SearchView, app: &mut AudioCloud) -> Task<Message>
                 });
             }
         }
+        SearchView::TempoOverlay => {
+            app.search_view_state.show_tempo_overlay = !app.search_view_state.show_tempo_overlay;
+        }
+        SearchView::MinTempoInput(val) => {
+            app.search_options.min_tempo = parse_tempo(&val);
+            app.search_view_state.min_tempo_input = val;
+            return app.debounce_search();
+        }
+        SearchView::MaxTempoInput(val) => {
+            app.search_options.max_tempo = parse_tempo(&val);
+            app.search_view_state.max_tempo_input = val;
+            return app.debounce_search();
+        }
+        SearchView::TempoPreset(min, max) => {
+            app.search_options.min_tempo = Some(min);
+            app.search_options.max_tempo = Some(max);
+            app.search_view_state.min_tempo_input = min.to_string();
+            app.search_view_state.max_tempo_input = max.to_string();
+            return app.create_request_command(app.input.clone());
+        }
+        SearchView::HalfDoubleTime(val) => {
+            app.search_options.half_double_time = val;
+            return app.create_request_command(app.input.clone());
+        }
+        SearchView::ClearTempo => {
+            app.search_options.min_tempo = None;
+            app.search_options.max_tempo = None;
+            app.search_view_state.min_tempo_input = String::new();
+            app.search_view_state.max_tempo_input = String::new();
+            return app.create_request_command(app.input.clone());
+        }
+        SearchView::Source(source) => {
+            app.search_options.source = source;
+            app.local_results = None;
+            return app.create_request_command(app.input.clone());
+        }
+        SearchView::Sort(mode) => {
+            app.settings.sort_mode = mode;
+        }
+        SearchView::Group(mode) => {
+            app.settings.group_mode = mode;
+        }
+        SearchView::HistoryOverlay => {
+            app.search_view_state.show_history_overlay =
+                !app.search_view_state.show_history_overlay;
+        }
+        SearchView::RunSearch(search) => return history::restore(app, search),
+        SearchView::SaveNameInput(val) => {
+            app.search_view_state.save_name_input = val;
+        }
+        SearchView::SaveSearch => {
+            let name = std::mem::take(&mut app.search_view_state.save_name_input);
+            history::save(app, name);
+        }
+        SearchView::PinHistory(i) => {
+            if let Some(search) = app.settings.search_history.get(i).cloned() {
+                app.settings
+                    .saved_searches
+                    .retain(|s| s.name != search.name);
+                app.settings.saved_searches.push(search);
+            }
+        }
+        SearchView::UnpinSearch(i) => {
+            if i < app.settings.saved_searches.len() {
+                app.settings.saved_searches.remove(i);
+            }
+        }
+        SearchView::ClearHistory => {
+            app.settings.search_history.clear();
+        }
         SearchView::GetPackIDS => {
             app.status
                 .set(crate::StatusBarLevel::Neutral, "Getting IDs");
             return Task::perform(
-                request::get_packs_meta(app.settings.server_url.clone()),
+                app.api
+                    .clone()
+                    .get_packs_meta(app.settings.server_url.clone()),
                 Message::PacksMetaRecived,
             );
         }
@@ -300,19 +662,57 @@ pub fn search_update(message: SearchView, app: &mut AudioCloud) -> Task<Message>
 #[derive(Clone, Debug)]
 pub enum SearchView {
     PackID(Option<String>),
+    RemovePack(String),
     PackOverlay,
     GetPackIDS,
+    TempoOverlay,
+    MinTempoInput(String),
+    MaxTempoInput(String),
+    TempoPreset(i32, i32),
+    HalfDoubleTime(bool),
+    ClearTempo,
+    Source(SearchSource),
+    Sort(SortMode),
+    Group(GroupMode),
+    HistoryOverlay,
+    RunSearch(SavedSearch),
+    SaveNameInput(String),
+    SaveSearch,
+    PinHistory(usize),
+    UnpinSearch(usize),
+    ClearHistory,
 }
 
 pub struct SearchViewState {
     pub show_pack_overlay: bool,
-    pub pack_id: Option<String>,
+    pub pack_ids: Vec<String>,
+    pub show_tempo_overlay: bool,
+    pub min_tempo_input: String,
+    pub max_tempo_input: String,
+    // Path of the highlighted sample, so it stays put when the order changes
+    pub selected: Option<String>,
+    pub show_history_overlay: bool,
+    pub save_name_input: String,
+    pub pages_loaded: usize,
+    pub has_more: bool,
+    pub loading_more: bool,
+    pub order: Vec<usize>,
 }
 impl SearchViewState {
     pub fn new() -> Self {
         SearchViewState {
             show_pack_overlay: false,
-            pack_id: None,
+            pack_ids: vec![],
+            show_tempo_overlay: false,
+            min_tempo_input: String::new(),
+            max_tempo_input: String::new(),
+            selected: None,
+            show_history_overlay: false,
+            save_name_input: String::new(),
+            pages_loaded: 0,
+            has_more: false,
+            loading_more: false,
+            order: vec![],
         }
     }
 }
@@ -322,6 +722,10 @@ pub struct SearchOptions {
     pub show_loops: bool,
     pub show_only_favourites: bool,
     pub show_all_favourites: bool,
+    pub min_tempo: Option<i32>,
+    pub max_tempo: Option<i32>,
+    pub half_double_time: bool,
+    pub source: SearchSource,
 }
 impl SearchOptions {
     pub fn new() -> Self {
@@ -330,25 +734,173 @@ impl SearchOptions {
             show_loops: true,
             show_only_favourites: false,
             show_all_favourites: false,
+            min_tempo: None,
+            max_tempo: None,
+            half_double_time: false,
+            source: SearchSource::Server,
+        }
+    }
+
+    // Range sent to the server, widened to cover half and double time if enabled
+    pub fn tempo_range(&self) -> (Option<i32>, Option<i32>) {
+        if self.half_double_time {
+            (self.min_tempo.map(|t| t / 2), self.max_tempo.map(|t| t * 2))
+        } else {
+            (self.min_tempo, self.max_tempo)
         }
     }
+
+    // The widened server range also matches everything in between, so filter again locally
+    pub fn tempo_matches(&self, sampletype: &SampleType) -> bool {
+        let tempo = match sampletype {
+            SampleType::OneShot => return true,
+            SampleType::Loop(tempo) => *tempo as i32,
+        };
+        let in_range = |factor: f32| {
+            let min = self
+                .min_tempo
+                .map_or(true, |t| tempo as f32 >= t as f32 * factor);
+            let max = self
+                .max_tempo
+                .map_or(true, |t| tempo as f32 <= t as f32 * factor);
+            min && max
+        };
+        if self.half_double_time {
+            in_range(1.0) || in_range(0.5) || in_range(2.0)
+        } else {
+            in_range(1.0)
+        }
+    }
+
+    pub fn tempo_label(&self) -> String {
+        match (self.min_tempo, self.max_tempo) {
+            (None, None) => String::from("Tempo"),
+            (Some(min), None) => format!("> {} bpm", min),
+            (None, Some(max)) => format!("< {} bpm", max),
+            (Some(min), Some(max)) => format!("{}-{} bpm", min, max),
+        }
+    }
+}
+
+fn parse_tempo(input: &str) -> Option<i32> {
+    match input.trim().parse::<i32>() {
+        Ok(val) if val > 0 => Some(val),
+        _ => None,
+    }
+}
+
+const TEMPO_PRESETS: [(i32, i32); 5] = [(80, 100), (100, 115), (120, 130), (140, 150), (170, 175)];
+
+fn tempo_selector(app: &AudioCloud) -> Element<Message> {
+    let inputs = row![
+        text_input("Min", &app.search_view_state.min_tempo_input)
+            .on_input(|val| Message::SearchView(SearchView::MinTempoInput(val)))
+            .width(Length::Fixed(70.0)),
+        text("-"),
+        text_input("Max", &app.search_view_state.max_tempo_input)
+            .on_input(|val| Message::SearchView(SearchView::MaxTempoInput(val)))
+            .width(Length::Fixed(70.0)),
+        text("bpm"),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let mut presets = row![].spacing(5);
+    for (min, max) in TEMPO_PRESETS {
+        presets = presets.push(
+            button(text(format!("{}-{}", min, max)).size(14))
+                .style(button::secondary)
+                .on_press(Message::SearchView(SearchView::TempoPreset(min, max))),
+        );
+    }
+
+    let half_double = checkbox(
+        "Include half/double time",
+        app.search_options.half_double_time,
+    )
+    .on_toggle(|val| Message::SearchView(SearchView::HalfDoubleTime(val)));
+
+    let clear = button(text("Any tempo"))
+        .style(button::text)
+        .on_press(Message::SearchView(SearchView::ClearTempo));
+
+    container(column![inputs, presets, half_double, clear].spacing(10))
+        .style(container::rounded_box)
+        .padding(10)
+        .into()
 }
 
-fn pack_selector(meta: &Vec<PackInfo>) -> Element<Message> {
-    let mut list = column![];
+fn pack_selector<'a>(meta: &'a Vec<PackInfo>, selected: &Vec<String>) -> Element<'a, Message> {
+    let all_label = match selected.is_empty() {
+        true => row![
+            text(icon_to_string(Bootstrap::CheckLg)).font(ICON_FONT),
+            text("All packs")
+        ],
+        false => row![text("All packs")],
+    };
+    let mut list = column![button(all_label.spacing(10).padding(10))
+        .style(button::text)
+        .on_press(Message::SearchView(SearchView::PackID(None)))];
     for pack in meta {
+        let mut name = row![].spacing(10);
+        if selected.contains(&pack.id) {
+            name = name.push(text(icon_to_string(Bootstrap::CheckLg)).font(ICON_FONT));
+        }
+        name = name.push(text(pack.name.clone()));
+        let mirror = tooltip(
+            button(text(icon_to_string(Bootstrap::CloudDownload)).font(ICON_FONT))
+                .style(button::text)
+                .on_press(Message::Download(DownloadEvent::Batch(BatchSource::Pack(
+                    pack.id.clone(),
+                )))),
+            text("Download pack"),
+            Position::Left,
+        )
+        .gap(10)
+        .style(container::rounded_box);
         list = list.push(
-            button(
-                column![text(pack.name.clone()), text(pack.description.clone())]
-                    .padding(10)
-                    .spacing(10),
-            )
-            .style(button::text)
-            .on_press(Message::Nothing(())),
+            row![
+                button(
+                    column![name, text(pack.description.clone())]
+                        .padding(10)
+                        .spacing(10),
+                )
+                .style(button::text)
+                .width(Length::Fill)
+                .on_press(Message::SearchView(SearchView::PackID(Some(
+                    pack.id.clone(),
+                )))),
+                mirror
+            ]
+            .align_y(Alignment::Center),
         )
     }
-    container(list.push(text("All packs")))
+    container(list)
         .style(container::rounded_box)
         .padding(10)
         .into()
 }
+
+fn pack_chips<'a>(meta: &'a Vec<PackInfo>, selected: &'a Vec<String>) -> Element<'a, Message> {
+    let mut chips = row![].spacing(5);
+    for id in selected {
+        let name = match meta.iter().find(|pack| &pack.id == id) {
+            Some(pack) => pack.name.clone(),
+            None => id.clone(),
+        };
+        chips = chips.push(
+            button(
+                row![
+                    text(name).size(14),
+                    text(icon_to_string(Bootstrap::X)).font(ICON_FONT).size(14)
+                ]
+                .spacing(5)
+                .align_y(Alignment::Center),
+            )
+            .style(themes::round_button)
+            .padding([2, 8])
+            .on_press(Message::SearchView(SearchView::RemovePack(id.clone()))),
+        );
+    }
+    chips.into()
+}
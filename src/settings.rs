@@ -4,13 +4,21 @@ use iced::Theme;
 use serde_derive::*;
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 
-use crate::helpers::{self, hash_sample};
+use crate::audio;
+use crate::cache;
+use crate::history::SavedSearch;
+use crate::keymap::{self, Action, KeyChord, Keymap};
+use crate::library;
+use crate::request::{ApiClient, ApiConfig};
+use crate::sorting::{GroupMode, SortMode};
+use crate::status::StatusBarLevel;
 use crate::AudioCloud;
 use crate::Message;
 use iced::widget::{
-    button, checkbox, column, combo_box, container, horizontal_space, row, scrollable, slider,
-    text, text_input, toggler,
+    button, checkbox, column, combo_box, container, horizontal_space, pick_list, row, scrollable,
+    slider, text, text_input, toggler,
 };
 use iced::Task;
 use iced::{alignment, Alignment, Element, Length, Padding};
@@ -23,16 +31,41 @@ use crate::ICON_FONT;
 #[derive(Debug, Clone)]
 pub enum SettingsChanged {
     ShowGradient(bool),
+    LoopPreview(bool),
     TitleSetting(SearchViewTitle),
+    RequestTimeout(u32),
+    RequestRetries(u32),
+    ApplyRequestConfig,
+    SearchDebounce(u32),
+    CacheLimit(u32),
+    ApplyCacheLimit,
+    PurgeServerCache(String),
+    OutputDevice(String),
+    ReconnectAudio,
+    RecordBinding(Action),
+    RemoveBinding(KeyChord),
+    RestoreKeymap,
+    LibraryFolderInput(String),
+    AddLibraryFolder,
+    RemoveLibraryFolder(usize),
+    RescanLibrary,
 }
 
 pub struct SettingsState {
     title_mode_state: combo_box::State<SearchViewTitle>,
+    pub output_devices: Vec<String>,
+    pub recording: Option<Action>,
+    pub keymap_conflict: Option<(Action, String)>,
+    pub library_folder_input: String,
 }
 impl SettingsState {
     pub fn new() -> Self {
         SettingsState {
             title_mode_state: combo_box::State::new(SearchViewTitle::all()),
+            output_devices: vec![],
+            recording: None,
+            keymap_conflict: None,
+            library_folder_input: String::new(),
         }
     }
 }
@@ -42,9 +75,101 @@ pub fn settings_changed(app: &mut AudioCloud, message: SettingsChanged) -> Task<
         SettingsChanged::ShowGradient(val) => {
             app.settings.searchbar_gradient = val;
         }
+        SettingsChanged::LoopPreview(val) => {
+            app.settings.loop_preview = val;
+        }
         SettingsChanged::TitleSetting(set) => {
             app.settings.searchview_title = Some(set);
         }
+        SettingsChanged::RequestTimeout(val) => {
+            app.settings.request_timeout = val;
+        }
+        SettingsChanged::RequestRetries(val) => {
+            app.settings.request_retries = val;
+        }
+        // A new client drops its connection pool, so it is only built once the slider is released
+        SettingsChanged::ApplyRequestConfig => {
+            app.api = ApiClient::new(app.settings.api_config());
+        }
+        SettingsChanged::SearchDebounce(val) => {
+            app.settings.search_debounce_ms = val;
+        }
+        SettingsChanged::CacheLimit(val) => {
+            app.settings.cache_limit_mb = val;
+        }
+        // Only evict once the slider is released, not for every step while dragging
+        SettingsChanged::ApplyCacheLimit => {
+            app.cache
+                .evict(app.settings.cache_limit_mb as u64 * 1024 * 1024);
+            return cache::save(app);
+        }
+        SettingsChanged::PurgeServerCache(server) => {
+            app.cache.purge_server(&server);
+            app.status.set(
+                StatusBarLevel::Neutral,
+                &format!("Purged cache of {}", server),
+            );
+            return cache::save(app);
+        }
+        SettingsChanged::OutputDevice(name) => {
+            app.settings.output_device = match name.as_str() {
+                audio::DEFAULT_OUTPUT => None,
+                _ => Some(name),
+            };
+            audio::reconnect(app);
+        }
+        SettingsChanged::ReconnectAudio => {
+            audio::reconnect(app);
+            return Task::perform(audio::list_output_devices(), Message::AudioDevicesListed);
+        }
+        SettingsChanged::RecordBinding(action) => {
+            app.settings_state.recording = Some(action);
+            app.settings_state.keymap_conflict = None;
+        }
+        SettingsChanged::RemoveBinding(chord) => {
+            app.settings.keymap.unbind(&chord);
+        }
+        SettingsChanged::RestoreKeymap => {
+            app.settings.keymap = Keymap::default();
+            app.settings_state.recording = None;
+            app.settings_state.keymap_conflict = None;
+        }
+        SettingsChanged::LibraryFolderInput(val) => {
+            app.settings_state.library_folder_input = val;
+        }
+        SettingsChanged::AddLibraryFolder => {
+            let folder = app.settings_state.library_folder_input.trim().to_string();
+            if folder.is_empty() || app.settings.library_folders.contains(&folder) {
+                return Task::none();
+            }
+            if !Path::new(&folder).is_dir() {
+                app.status.set(
+                    StatusBarLevel::Danger,
+                    &format!("{} is not a folder", folder),
+                );
+                return Task::none();
+            }
+            app.settings.library_folders.push(folder);
+            app.settings_state.library_folder_input.clear();
+            return library::rescan(app);
+        }
+        SettingsChanged::RemoveLibraryFolder(i) => {
+            if i < app.settings.library_folders.len() {
+                app.settings.library_folders.remove(i);
+            }
+            // Nothing left to scan, so the rescan wouldn't drop the old entries
+            if app.settings.library_folders.is_empty() {
+                app.library = library::LibraryIndex {
+                    loaded: true,
+                    ..Default::default()
+                };
+                return Task::perform(library::save_index(app.library.clone()), Message::Nothing);
+            }
+            return library::rescan(app);
+        }
+        SettingsChanged::RescanLibrary => {
+            return library::rescan_all(app);
+        }
     }
     Task::none()
 }
@@ -82,7 +207,47 @@ pub struct Settings {
     pub server_url: String,
     pub max_results: i32,
     pub favourite_samples: Vec<Sample>,
-    pub dl_samples_hash: Vec<String>,
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u32,
+    #[serde(default = "default_request_retries")]
+    pub request_retries: u32,
+    #[serde(default = "default_cache_limit")]
+    pub cache_limit_mb: u32,
+    #[serde(default = "default_loop_preview")]
+    pub loop_preview: bool,
+    #[serde(default)]
+    pub output_device: Option<String>,
+    #[serde(default)]
+    pub keymap: Keymap,
+    #[serde(default = "default_search_debounce")]
+    pub search_debounce_ms: u32,
+    #[serde(default)]
+    pub search_history: Vec<SavedSearch>,
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearch>,
+    #[serde(default)]
+    pub sort_mode: SortMode,
+    #[serde(default)]
+    pub group_mode: GroupMode,
+    #[serde(default)]
+    pub recently_played: Vec<String>,
+    #[serde(default)]
+    pub library_folders: Vec<String>,
+}
+fn default_request_timeout() -> u32 {
+    ApiConfig::default().timeout.as_secs() as u32
+}
+fn default_request_retries() -> u32 {
+    ApiConfig::default().retries
+}
+fn default_cache_limit() -> u32 {
+    2048
+}
+fn default_loop_preview() -> bool {
+    true
+}
+fn default_search_debounce() -> u32 {
+    250
 }
 pub async fn load_from_file(path: &str) -> Settings {
     if !Path::new(path).exists() {
@@ -118,17 +283,12 @@ impl Settings {
             }
         }
     }
-    pub fn is_downloaded(&self, path: &str) -> bool {
-        for entry in &self.dl_samples_hash {
-            if entry == &hash_sample(&path.replace(".wav", "")) {
-                return true;
-            }
+    pub fn api_config(&self) -> ApiConfig {
+        ApiConfig {
+            timeout: Duration::from_secs(self.request_timeout as u64),
+            retries: self.request_retries,
+            ..ApiConfig::default()
         }
-        false
-    }
-    pub fn add_dl_entry(&mut self, path: &str) {
-        self.dl_samples_hash.push(helpers::hash_sample(path));
-        println!("added dl entry: {}", helpers::hash_sample(path));
     }
     pub fn default() -> Self {
         Settings {
@@ -138,7 +298,19 @@ impl Settings {
             server_url: "http://127.0.0.1:4040/".to_string(),
             theme: "Dark".to_string(),
             favourite_samples: vec![],
-            dl_samples_hash: vec![],
+            request_timeout: default_request_timeout(),
+            request_retries: default_request_retries(),
+            cache_limit_mb: default_cache_limit(),
+            loop_preview: default_loop_preview(),
+            output_device: None,
+            keymap: Keymap::default(),
+            search_debounce_ms: default_search_debounce(),
+            search_history: vec![],
+            saved_searches: vec![],
+            sort_mode: SortMode::Server,
+            group_mode: GroupMode::None,
+            recently_played: vec![],
+            library_folders: vec![],
         }
     }
 }
@@ -168,6 +340,60 @@ pub fn settings(app: &AudioCloud) -> Element<Message> {
         },
         None => text("Unknown status"),
     };
+    let mut cache_usage = column![].spacing(10).padding(Padding {
+        top: 0.0,
+        bottom: 0.0,
+        left: 40.0,
+        right: 20.0,
+    });
+    for (server, size) in app.cache.usage_per_server() {
+        cache_usage = cache_usage.push(
+            row![
+                text(server.clone()).style(themes::text_fg),
+                text(format!("{:.1} MB", size as f32 / (1024.0 * 1024.0))),
+                button(text("Purge"))
+                    .style(button::danger)
+                    .on_press(Message::Settings(SettingsChanged::PurgeServerCache(server)))
+            ]
+            .align_y(Alignment::Center)
+            .spacing(15),
+        );
+    }
+
+    let mut device_options = vec![audio::DEFAULT_OUTPUT.to_string()];
+    device_options.extend(app.settings_state.output_devices.iter().cloned());
+    let selected_device = match &app.settings.output_device {
+        Some(name) => name.clone(),
+        None => audio::DEFAULT_OUTPUT.to_string(),
+    };
+    let device_status = match &app.audio_devices {
+        Some(devs) => text(format!("Using {}", devs.device)).style(themes::text_fg),
+        None => text("No audio output").style(text::danger),
+    };
+
+    let mut library_folders = column![].spacing(10).padding(Padding {
+        top: 0.0,
+        bottom: 0.0,
+        left: 40.0,
+        right: 20.0,
+    });
+    for (i, folder) in app.settings.library_folders.iter().enumerate() {
+        library_folders = library_folders.push(
+            row![
+                text(folder.clone()).style(themes::text_fg),
+                button(text("Remove"))
+                    .style(button::danger)
+                    .on_press(Message::Settings(SettingsChanged::RemoveLibraryFolder(i)))
+            ]
+            .align_y(Alignment::Center)
+            .spacing(15),
+        );
+    }
+    let library_status = match app.library_scanning {
+        true => text("Scanning..."),
+        false => text(format!("{} samples indexed", app.library.entries.len())),
+    };
+
     let settings = column![
         row![
             text("Server URL:"),
@@ -212,7 +438,26 @@ pub fn settings(app: &AudioCloud) -> Element<Message> {
         .padding(20)
         .spacing(15),
         row![
-            text("Max search results:"),
+            text("Output device:"),
+            pick_list(device_options, Some(selected_device), |name| {
+                Message::Settings(SettingsChanged::OutputDevice(name))
+            }),
+            button(text("Reconnect audio"))
+                .on_press(Message::Settings(SettingsChanged::ReconnectAudio)),
+            device_status,
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15)
+        .padding(20),
+        row![
+            checkbox("Loop previews of loops", app.settings.loop_preview)
+                .on_toggle(|val| Message::Settings(SettingsChanged::LoopPreview(val)))
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15)
+        .padding(20),
+        row![
+            text("Results per page:"),
             text(app.settings.max_results),
             slider(
                 std::ops::RangeInclusive::new(1, 100),
@@ -223,6 +468,76 @@ pub fn settings(app: &AudioCloud) -> Element<Message> {
         .align_y(Alignment::Center)
         .spacing(15)
         .padding(20),
+        row![
+            text("Search delay:"),
+            text(format!("{}ms", app.settings.search_debounce_ms)),
+            slider(
+                std::ops::RangeInclusive::new(0, 1000),
+                app.settings.search_debounce_ms,
+                |val| Message::Settings(SettingsChanged::SearchDebounce(val))
+            )
+            .step(50u32)
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15)
+        .padding(20),
+        row![
+            text("Request timeout:"),
+            text(format!("{}s", app.settings.request_timeout)),
+            slider(
+                std::ops::RangeInclusive::new(1, 60),
+                app.settings.request_timeout,
+                |val| Message::Settings(SettingsChanged::RequestTimeout(val))
+            )
+            .on_release(Message::Settings(SettingsChanged::ApplyRequestConfig)),
+            text("Retries:"),
+            text(app.settings.request_retries),
+            slider(
+                std::ops::RangeInclusive::new(0, 5),
+                app.settings.request_retries,
+                |val| Message::Settings(SettingsChanged::RequestRetries(val))
+            )
+            .on_release(Message::Settings(SettingsChanged::ApplyRequestConfig))
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15)
+        .padding(20),
+        row![
+            text("Cache size:"),
+            text(format!(
+                "{:.1} / {} MB",
+                app.cache.download_size() as f32 / (1024.0 * 1024.0),
+                app.settings.cache_limit_mb
+            )),
+            slider(
+                std::ops::RangeInclusive::new(128, 16384),
+                app.settings.cache_limit_mb,
+                |val| Message::Settings(SettingsChanged::CacheLimit(val))
+            )
+            .step(128u32)
+            .on_release(Message::Settings(SettingsChanged::ApplyCacheLimit)),
+            text("Editor exports are never removed").style(themes::text_fg)
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15)
+        .padding(20),
+        cache_usage,
+        row![
+            text("Library folders:"),
+            text_input("/path/to/samples", &app.settings_state.library_folder_input)
+                .on_input(|val| Message::Settings(SettingsChanged::LibraryFolderInput(val)))
+                .on_submit(Message::Settings(SettingsChanged::AddLibraryFolder)),
+            button(text("Add")).on_press(Message::Settings(SettingsChanged::AddLibraryFolder)),
+            button(text("Rescan"))
+                .style(button::secondary)
+                .on_press(Message::Settings(SettingsChanged::RescanLibrary)),
+            library_status.style(themes::text_fg),
+        ]
+        .align_y(Alignment::Center)
+        .spacing(15)
+        .padding(20),
+        library_folders,
+        keymap::keymap_editor(app),
         row![
             button(text("Save settings"))
                 .on_press(Message::SaveSettings)
@@ -240,5 +555,7 @@ pub fn settings(app: &AudioCloud) -> Element<Message> {
         .padding(20),
     ];
 
-    column![status_bar, title, settings].spacing(20).into()
+    column![status_bar, title, scrollable(settings)]
+        .spacing(20)
+        .into()
 }
@@ -0,0 +1,179 @@
+use audiocloud_lib::{PackInfo, Sample};
+use core::fmt;
+use serde_derive::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::library;
+use crate::{AudioCloud, SampleType};
+
+const MAX_RECENTLY_PLAYED: usize = 200;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortMode {
+    #[default]
+    Server,
+    Name,
+    Tempo,
+    Type,
+    Pack,
+    RecentlyPlayed,
+    FavouritesFirst,
+}
+impl SortMode {
+    pub fn all() -> Vec<Self> {
+        vec![
+            SortMode::Server,
+            SortMode::Name,
+            SortMode::Tempo,
+            SortMode::Type,
+            SortMode::Pack,
+            SortMode::RecentlyPlayed,
+            SortMode::FavouritesFirst,
+        ]
+    }
+}
+impl std::fmt::Display for SortMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortMode::Server => write!(f, "Server order"),
+            SortMode::Name => write!(f, "Name"),
+            SortMode::Tempo => write!(f, "BPM"),
+            SortMode::Type => write!(f, "Type"),
+            SortMode::Pack => write!(f, "Pack"),
+            SortMode::RecentlyPlayed => write!(f, "Recently played"),
+            SortMode::FavouritesFirst => write!(f, "Favourites first"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum GroupMode {
+    #[default]
+    None,
+    Pack,
+    Folder,
+}
+impl GroupMode {
+    pub fn all() -> Vec<Self> {
+        vec![GroupMode::None, GroupMode::Pack, GroupMode::Folder]
+    }
+}
+impl std::fmt::Display for GroupMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupMode::None => write!(f, "No grouping"),
+            GroupMode::Pack => write!(f, "Group by pack"),
+            GroupMode::Folder => write!(f, "Group by folder"),
+        }
+    }
+}
+
+// Folders no pack claims keep their own name
+pub fn pack_name(path: &str, meta: &[PackInfo]) -> String {
+    if library::is_library_path(path) {
+        return String::from("Library");
+    }
+    let folder = path.split('/').find(|s| !s.is_empty()).unwrap_or("");
+    match meta
+        .iter()
+        .find(|p| p.id == folder)
+        .or_else(|| meta.iter().find(|p| p.name == folder))
+    {
+        Some(pack) => pack.name.clone(),
+        None => folder.to_string(),
+    }
+}
+
+pub fn folder_name(path: &str) -> String {
+    match path.trim_start_matches('/').rsplit_once('/') {
+        Some((folder, _)) => folder.to_string(),
+        None => String::from("/"),
+    }
+}
+
+pub fn group_key(mode: GroupMode, sample: &Sample, meta: &[PackInfo]) -> Option<String> {
+    match mode {
+        GroupMode::None => None,
+        GroupMode::Pack => Some(pack_name(&sample.path, meta)),
+        GroupMode::Folder => Some(folder_name(&sample.path)),
+    }
+}
+
+// Oneshots have no tempo and go after all loops
+fn tempo(sample: &Sample) -> Option<u32> {
+    match sample.sampletype {
+        SampleType::OneShot => None,
+        SampleType::Loop(tempo) => Some(tempo as u32),
+    }
+}
+
+fn sort_key(
+    app: &AudioCloud,
+    mode: SortMode,
+    sample: &Sample,
+    recent: &HashMap<&str, usize>,
+    favourites: &HashSet<&str>,
+) -> (usize, String) {
+    match mode {
+        SortMode::Server => (0, String::new()),
+        SortMode::Name => (0, sample.name.to_lowercase()),
+        SortMode::Tempo => match tempo(sample) {
+            Some(tempo) => (tempo as usize, String::new()),
+            None => (usize::MAX, String::new()),
+        },
+        SortMode::Type => (tempo(sample).is_some() as usize, String::new()),
+        SortMode::Pack => (0, pack_name(&sample.path, &app.pack_meta)),
+        SortMode::RecentlyPlayed => (
+            recent
+                .get(sample.path.as_str())
+                .copied()
+                .unwrap_or(usize::MAX),
+            String::new(),
+        ),
+        SortMode::FavouritesFirst => (
+            !favourites.contains(sample.path.as_str()) as usize,
+            String::new(),
+        ),
+    }
+}
+
+// Stable, so equal samples keep the server (or shuffled) order
+pub fn sort_samples(app: &AudioCloud, samples: &mut Vec<(usize, &Sample)>) {
+    let group = app.settings.group_mode;
+    let mode = app.settings.sort_mode;
+    if mode == SortMode::Server && group == GroupMode::None {
+        return;
+    }
+    let recent: HashMap<&str, usize> = app
+        .settings
+        .recently_played
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (path.as_str(), i))
+        .collect();
+    let favourites: HashSet<&str> = app
+        .settings
+        .favourite_samples
+        .iter()
+        .map(|s| s.path.as_str())
+        .collect();
+    let mut keyed: Vec<_> = samples
+        .drain(..)
+        .map(|item| {
+            let key = (
+                group_key(group, item.1, &app.pack_meta),
+                sort_key(app, mode, item.1, &recent, &favourites),
+            );
+            (key, item)
+        })
+        .collect();
+    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+    samples.extend(keyed.into_iter().map(|(_, item)| item));
+}
+
+pub fn played(app: &mut AudioCloud, path: &str) {
+    let recent = &mut app.settings.recently_played;
+    recent.retain(|p| p != path);
+    recent.insert(0, path.to_string());
+    recent.truncate(MAX_RECENTLY_PLAYED);
+}
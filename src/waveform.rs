@@ -1,13 +1,37 @@
+use crate::audio::Decoded;
 use crate::ARRAYLEN;
 use iced::advanced::layout::{self, Layout};
 use iced::advanced::renderer;
-use iced::advanced::widget::{self, Widget};
-use iced::mouse;
+use iced::advanced::widget::{self, tree, Widget};
+use iced::advanced::{Clipboard, Shell};
+use iced::{event, mouse, Event};
 use iced::{Border, Color, Element, Length, Rectangle, Size};
 use std::sync::{Arc, RwLock};
-pub struct Waveform {
+
+// Pixels around a trim handle that still grab it, and the smallest trimmed length
+const HANDLE_GRAB: f32 = 8.0;
+const MIN_TRIM: f32 = 0.01;
+
+pub struct Waveform<'a, Message> {
     color: Color,
     vals: [f32; ARRAYLEN as usize],
+    playhead: Option<f32>,
+    region: Option<(f32, f32)>,
+    trim: Option<(f32, f32)>,
+    on_seek: Option<Box<dyn Fn(f32) -> Message + 'a>>,
+    on_trim: Option<Box<dyn Fn(f32, f32) -> Message + 'a>>,
+    on_trim_release: Option<Message>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Handle {
+    Start,
+    End,
+}
+
+#[derive(Default)]
+struct State {
+    dragging: Option<Handle>,
 }
 
 pub fn get_waveform(mut samples_audio: Vec<f32>) -> [f32; ARRAYLEN as usize] {
@@ -41,27 +65,94 @@ pub async fn get_waveform_tk(samples_audio: Arc<RwLock<Vec<f32>>>) -> [f32; ARRA
     get_waveform_readonly(samples_audio)
 }
 
-impl Waveform {
+// Samples shorter than ARRAYLEN are padded with silence
+pub async fn get_waveform_decoded(audio: Arc<Decoded>) -> [f32; ARRAYLEN as usize] {
+    let mut arr: [f32; ARRAYLEN as usize] = [0.0; ARRAYLEN as usize];
+    let samples = &audio.samples;
+    let divider = (samples.len() / ARRAYLEN as usize).max(1);
+    for (i, chunk) in samples.chunks(divider).take(ARRAYLEN as usize).enumerate() {
+        let sum: f32 = chunk.iter().map(|v| v * v).sum();
+        arr[i] = (sum / chunk.len() as f32).sqrt();
+    }
+    arr
+}
+
+impl<'a, Message> Waveform<'a, Message> {
     fn new(vals: [f32; ARRAYLEN as usize]) -> Self {
         Waveform {
             color: Color::BLACK,
             vals,
+            playhead: None,
+            region: None,
+            trim: None,
+            on_seek: None,
+            on_trim: None,
+            on_trim_release: None,
         }
     }
     pub fn color(mut self, color: Color) -> Self {
         self.color = color;
         self
     }
+    // Position of the playhead from 0.0 to 1.0, the part already played is drawn brighter
+    pub fn playhead(mut self, position: Option<f32>) -> Self {
+        self.playhead = position;
+        self
+    }
+    // Highlighted part from 0.0 to 1.0, like a loop region
+    pub fn region(mut self, region: Option<(f32, f32)>) -> Self {
+        self.region = region;
+        self
+    }
+    pub fn on_seek(mut self, f: impl Fn(f32) -> Message + 'a) -> Self {
+        self.on_seek = Some(Box::new(f));
+        self
+    }
+    // Draws draggable handles at both ends of the trim, the audio outside is dimmed
+    pub fn trim(mut self, trim: (f32, f32)) -> Self {
+        self.trim = Some(trim);
+        self
+    }
+    pub fn on_trim(mut self, f: impl Fn(f32, f32) -> Message + 'a) -> Self {
+        self.on_trim = Some(Box::new(f));
+        self
+    }
+    pub fn on_trim_release(mut self, message: Message) -> Self {
+        self.on_trim_release = Some(message);
+        self
+    }
+
+    fn handle_at(&self, x: f32, width: f32) -> Option<Handle> {
+        let (start, end) = self.trim?;
+        self.on_trim.as_ref()?;
+        let grab = HANDLE_GRAB / width;
+        if (x - start).abs() < grab {
+            Some(Handle::Start)
+        } else if (x - end).abs() < grab {
+            Some(Handle::End)
+        } else {
+            None
+        }
+    }
 }
 
-pub fn waveform(vals: [f32; ARRAYLEN as usize]) -> Waveform {
+pub fn waveform<'a, Message>(vals: [f32; ARRAYLEN as usize]) -> Waveform<'a, Message> {
     Waveform::new(vals)
 }
 
-impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer> for Waveform
+impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer> for Waveform<'a, Message>
 where
+    Message: Clone,
     Renderer: renderer::Renderer,
 {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
     fn size(&self) -> Size<Length> {
         Size {
             width: Length::Fill,
@@ -99,7 +190,9 @@ where
         let steps: i32 = ARRAYLEN;
         let step_width = bounds.width / steps as f32;
 
-        let playing_step = ((10.0 / 100.0) * steps as f32).round() as i32;
+        let playing_step = self
+            .playhead
+            .map(|p| (p.clamp(0.0, 1.0) * steps as f32).round() as i32);
 
         //Scale Averages
         let max: f32 = self
@@ -108,17 +201,41 @@ where
             .copied()
             .max_by(|f1, f2| f1.total_cmp(f2))
             .unwrap();
-        let multiplier = 1.0 / max;
+        let multiplier = if max > 0.0 { 1.0 / max } else { 0.0 };
         let scaled_vals: Vec<f32> = self.vals.into_iter().map(|x| x * multiplier).collect();
 
+        if let Some((start, end)) = self.region {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: start_x + start.clamp(0.0, 1.0) * bounds.width,
+                        y,
+                        width: (end - start).clamp(0.0, 1.0) * bounds.width,
+                        height,
+                    },
+                    ..renderer::Quad::default()
+                },
+                Color {
+                    a: self.color.a * 0.15,
+                    ..self.color
+                },
+            );
+        }
+
         for i in 0..steps {
             let val = scaled_vals[i as usize];
             let l_height = val * height;
-            let color = if (playing_step - i).abs() < 4 {
-                //Color::BLACK
-                self.color
-            } else {
-                self.color
+            let pos = i as f32 / steps as f32;
+            let color = match (playing_step, self.trim) {
+                (_, Some((start, end))) if pos < start || pos >= end => Color {
+                    a: self.color.a * 0.2,
+                    ..self.color
+                },
+                (Some(step), _) if i >= step => Color {
+                    a: self.color.a * 0.4,
+                    ..self.color
+                },
+                _ => self.color,
             };
 
             let rec = Rectangle {
@@ -140,14 +257,144 @@ where
                 color,
             );
         }
+
+        if let Some(step) = playing_step {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: start_x + step as f32 * step_width - 1.0,
+                        y,
+                        width: 2.0,
+                        height,
+                    },
+                    ..renderer::Quad::default()
+                },
+                self.color,
+            );
+        }
+
+        if let Some((start, end)) = self.trim {
+            for pos in [start, end] {
+                let x = start_x + pos.clamp(0.0, 1.0) * bounds.width;
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle {
+                            x: x - 1.5,
+                            y,
+                            width: 3.0,
+                            height,
+                        },
+                        ..renderer::Quad::default()
+                    },
+                    Color::WHITE,
+                );
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle {
+                            x: x - 5.0,
+                            y,
+                            width: 10.0,
+                            height: 10.0,
+                        },
+                        border: Border {
+                            color: Color::WHITE,
+                            width: 0.0,
+                            radius: 2.0.into(),
+                        },
+                        ..renderer::Quad::default()
+                    },
+                    Color::WHITE,
+                );
+            }
+        }
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut widget::Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        let state = tree.state.downcast_mut::<State>();
+        let bounds = layout.bounds();
+        match event {
+            // Handles take the click before seeking does
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let x = match cursor.position_in(bounds) {
+                    None => return event::Status::Ignored,
+                    Some(position) => position.x / bounds.width,
+                };
+                if let Some(handle) = self.handle_at(x, bounds.width) {
+                    state.dragging = Some(handle);
+                    return event::Status::Captured;
+                }
+                if let Some(on_seek) = &self.on_seek {
+                    shell.publish(on_seek(x));
+                    return event::Status::Captured;
+                }
+            }
+            // Dragging keeps going when the cursor leaves the waveform
+            Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                if let (Some(handle), Some((start, end)), Some(on_trim)) =
+                    (state.dragging, self.trim, &self.on_trim)
+                {
+                    let x = ((position.x - bounds.x) / bounds.width).clamp(0.0, 1.0);
+                    let trim = match handle {
+                        Handle::Start => (x.min(end - MIN_TRIM).max(0.0), end),
+                        Handle::End => (start, x.max(start + MIN_TRIM).min(1.0)),
+                    };
+                    shell.publish(on_trim(trim.0, trim.1));
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                if state.dragging.take().is_some() {
+                    if let Some(message) = &self.on_trim_release {
+                        shell.publish(message.clone());
+                    }
+                    return event::Status::Captured;
+                }
+            }
+            _ => (),
+        }
+        event::Status::Ignored
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &widget::Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let bounds = layout.bounds();
+        let over_handle = cursor
+            .position_in(bounds)
+            .and_then(|position| self.handle_at(position.x / bounds.width, bounds.width))
+            .is_some();
+        if over_handle || tree.state.downcast_ref::<State>().dragging.is_some() {
+            mouse::Interaction::ResizingHorizontally
+        } else if self.on_seek.is_some() && cursor.is_over(bounds) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
     }
 }
 
-impl<'a, Message, Theme, Renderer> From<Waveform> for Element<'a, Message, Theme, Renderer>
+impl<'a, Message, Theme, Renderer> From<Waveform<'a, Message>>
+    for Element<'a, Message, Theme, Renderer>
 where
+    Message: Clone + 'a,
     Renderer: renderer::Renderer,
 {
-    fn from(waveform: Waveform) -> Self {
+    fn from(waveform: Waveform<'a, Message>) -> Self {
         Self::new(waveform)
     }
 }
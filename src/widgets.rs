@@ -1,5 +1,7 @@
+use crate::audio::{self, format_duration};
 use crate::bootstrap::{ICON_FONT, *};
-use crate::{AudioCloud, Message};
+use crate::waveform::waveform;
+use crate::{AudioCloud, Message, ARRAYLEN};
 use iced::border::Radius;
 use iced::widget::{
     button, checkbox, column, combo_box, container, horizontal_rule, horizontal_space, row, rule,
@@ -9,13 +11,30 @@ use iced::{
     alignment, Alignment, Element, Executor, Font, Length, Padding, Subscription, Task, Theme,
 };
 use std::ops::RangeInclusive;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub struct Player {
     pub is_playing: bool,
     pub name: String,
     pub volume: f32,
-    pub last_update_playing: Instant,
+    pub audio: Option<Arc<audio::Decoded>>,
+    pub position: Duration,
+    pub duration: Option<Duration>,
+    pub last_tick: Instant,
+    pub wav: [f32; ARRAYLEN as usize],
+    pub looping: bool,
+}
+impl Player {
+    // Playhead from 0.0 to 1.0, None if nothing with a known length is loaded
+    pub fn progress(&self) -> Option<f32> {
+        match self.duration {
+            Some(dur) if !dur.is_zero() => {
+                Some((self.position.as_secs_f32() / dur.as_secs_f32()).min(1.0))
+            }
+            _ => None,
+        }
+    }
 }
 
 pub fn padding_now(num: i32) -> Padding {
@@ -62,10 +81,47 @@ pub fn player_widget(app: &AudioCloud) -> Element<Message> {
     .width(Length::Fixed(160.0))
     .step(0.01);
 
+    let time = match app.player.duration {
+        Some(dur) => format!(
+            "{} / {}",
+            format_duration(app.player.position),
+            format_duration(dur)
+        ),
+        None => format_duration(app.player.position),
+    };
+
+    let wav = container(
+        waveform(app.player.wav)
+            .color(app.theme().extended_palette().primary.base.color)
+            .playhead(app.player.progress())
+            .on_seek(Message::SeekPlayer),
+    )
+    .width(Length::FillPortion(3))
+    .height(Length::Fixed(30.0));
+
+    let loop_text = match app.player.looping {
+        true => text(icon_to_string(Bootstrap::Repeat)).style(text::success),
+        false => text(icon_to_string(Bootstrap::Repeat)),
+    };
+    let loop_button = button(loop_text.font(ICON_FONT).size(20))
+        .style(button::text)
+        .on_press(Message::ToggleLoop);
+
+    let stop_button = button(
+        text(icon_to_string(Bootstrap::Stop))
+            .font(ICON_FONT)
+            .size(25),
+    )
+    .style(button::text)
+    .on_press(Message::StopPlayer);
+
     let row = row![
         play_button,
+        stop_button,
+        loop_button,
         text(&app.player.name),
-        horizontal_space().width(Length::Fill),
+        text(time).style(crate::themes::text_fg),
+        wav,
         text(icon_to_string(vol_icon)).font(ICON_FONT).size(20),
         vol_slider,
     ]